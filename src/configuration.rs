@@ -5,6 +5,7 @@ use serde::export::Formatter;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
@@ -15,8 +16,335 @@ pub struct Configuration {
     pub credentials: Credentials,
     #[serde(default)]
     pub scenes: Vec<Scene>,
+    /// partial scenes, which can be stacked on top of the active scene
+    #[serde(default)]
+    pub overlays: Vec<Overlay>,
+    /// actions triggered by mqtt messages, e.g. of buttons
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
     pub sensors: Vec<Sensor>,
     pub switches: Vec<Switch>,
+    /// named groups of switches, which can be referenced in scenes
+    #[serde(default)]
+    pub groups: HashMap<String, Vec<String>>,
+    /// how rooms are connected with each other
+    #[serde(default)]
+    pub rooms: Vec<Room>,
+    /// number of people living in the home,
+    /// one current room is tracked per occupant
+    #[serde(default = "Configuration::default_occupants")]
+    pub occupants: usize,
+    /// brightness and color temperature curve over the day
+    #[serde(default)]
+    pub circadian: Option<Circadian>,
+    /// decision engine which controls the switches
+    #[serde(default = "Configuration::default_strategy")]
+    pub strategy: StrategyKind,
+    /// how sensors without any message are handled after start
+    #[serde(default = "Configuration::default_startup_policy")]
+    pub startup_policy: StartupPolicy,
+    /// publish Home Assistant mqtt discovery configs
+    #[serde(default)]
+    pub homeassistant: Option<HomeAssistant>,
+    /// mqtt settings of the control topics
+    #[serde(default = "Configuration::default_control")]
+    pub control: Control,
+    /// embedded http server
+    #[serde(default)]
+    pub http: Option<Http>,
+    /// battery and link quality monitoring of sensors and switches
+    #[serde(default)]
+    pub device_monitoring: Option<DeviceMonitoring>,
+    /// limit the rate of switch commands
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    /// away mode, when the whole home is absent
+    #[serde(default)]
+    pub away: Option<Away>,
+    /// presence simulation while on vacation
+    #[serde(default)]
+    pub vacation: Option<Vacation>,
+}
+
+/// Away mode, when the whole home is absent
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Away {
+    /// how long, in seconds, all rooms must be absent
+    #[serde(default = "Away::default_after")]
+    pub after: u64,
+    /// scene to activate while away, the previous scene is restored on presence
+    #[serde(default)]
+    pub scene: Option<String>,
+    /// topic to publish the away state
+    #[serde(default = "Away::default_topic")]
+    pub topic: String,
+    /// forget the current room while away, so tracking starts fresh
+    #[serde(default)]
+    pub clear_current_room: bool,
+    /// door sensor topics, away is activated if one of them was opened
+    /// and no motion followed within `door_timeout`
+    #[serde(default)]
+    pub doors: Vec<String>,
+    /// how long, in seconds, to wait for motion after a door was opened
+    #[serde(default = "Away::default_door_timeout")]
+    pub door_timeout: u64,
+}
+
+impl Away {
+    pub fn default_after() -> u64 {
+        1800
+    }
+
+    pub fn default_topic() -> String {
+        "home/away".to_string()
+    }
+
+    pub fn default_door_timeout() -> u64 {
+        300
+    }
+}
+
+/// Presence simulation while on vacation, replaying the recorded room presence
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Vacation {
+    /// file to record the room presence history
+    pub history_file: String,
+    /// how many past days are replayed
+    #[serde(default = "Vacation::default_days")]
+    pub days: u64,
+    /// max random shift, in seconds, of the replayed presence
+    #[serde(default = "Vacation::default_jitter")]
+    pub jitter: u64,
+}
+
+impl Vacation {
+    pub fn default_days() -> u64 {
+        7
+    }
+
+    pub fn default_jitter() -> u64 {
+        900
+    }
+}
+
+/// Limit the rate of switch commands, to not flood bridges
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RateLimit {
+    /// max commands per second of all switches, None is unlimited
+    #[serde(default)]
+    pub commands_per_second: Option<f64>,
+    /// max commands per second of command topics with a prefix,
+    /// the longest matching prefix is used
+    #[serde(default)]
+    pub prefixes: Vec<PrefixRateLimit>,
+}
+
+/// Rate limit of command topics with a prefix (e.g. a bridge)
+#[derive(Clone, Deserialize, Serialize)]
+pub struct PrefixRateLimit {
+    pub prefix: String,
+    pub commands_per_second: f64,
+}
+
+/// Battery and link quality monitoring of sensors and switches
+#[derive(Clone, Deserialize, Serialize)]
+pub struct DeviceMonitoring {
+    /// json key to read the battery level
+    #[serde(default = "DeviceMonitoring::default_battery_key")]
+    pub battery_key: String,
+    /// json key to read the link quality
+    #[serde(default = "DeviceMonitoring::default_linkquality_key")]
+    pub linkquality_key: String,
+    /// warn if the battery level is below
+    #[serde(default = "DeviceMonitoring::default_battery_threshold")]
+    pub battery_threshold: u64,
+    /// warn if the link quality is below
+    #[serde(default = "DeviceMonitoring::default_linkquality_threshold")]
+    pub linkquality_threshold: u64,
+}
+
+impl DeviceMonitoring {
+    pub fn default_battery_key() -> String {
+        "battery".to_string()
+    }
+
+    pub fn default_linkquality_key() -> String {
+        "linkquality".to_string()
+    }
+
+    pub fn default_battery_threshold() -> u64 {
+        20
+    }
+
+    pub fn default_linkquality_threshold() -> u64 {
+        20
+    }
+}
+
+/// Embedded http server, serving metrics and a json api
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Http {
+    /// address to listen on
+    #[serde(default = "Http::default_address")]
+    pub address: String,
+}
+
+impl Http {
+    pub fn default_address() -> String {
+        "127.0.0.1:9292".to_string()
+    }
+}
+
+/// Runs an action when a message with a value is received
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Trigger {
+    /// topic to listen to
+    pub topic: String,
+    /// json key to read the value
+    pub key: String,
+    /// value which triggers the action
+    pub value: Value,
+    pub action: TriggerAction,
+    /// qos to subscribe the topic
+    #[serde(default)]
+    pub qos: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerAction {
+    /// activate a scene
+    Scene(String),
+    /// activate the scene after the active scene in the list
+    Cycle(Vec<String>),
+    /// enable room tracking if it is disabled and vice versa
+    ToggleRoomTracking,
+    /// change the brightness of the active scene
+    Brightness(u8),
+}
+
+/// Partial scene, which is stacked on top of the active scene.
+/// Only the defined fields change the scene.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Overlay {
+    pub name: String,
+    #[serde(default)]
+    pub brightness: Option<u8>,
+    #[serde(default)]
+    pub color_temp: Option<u16>,
+    #[serde(default)]
+    pub color: Option<Color>,
+    /// merged into the room settings of the scene
+    #[serde(default)]
+    pub room_settings: HashMap<String, LightSettings>,
+    /// merged into the switch settings of the scene
+    #[serde(default)]
+    pub switch_settings: HashMap<String, LightSettings>,
+    /// added to the disabled switches of the scene
+    #[serde(default)]
+    pub disabled_switches: Vec<String>,
+    /// added to the enabled switches of the scene
+    #[serde(default)]
+    pub enabled_switches: Vec<String>,
+    /// added to the ignored switches of the scene
+    #[serde(default)]
+    pub ignored_switches: Vec<String>,
+    #[serde(default)]
+    pub room_tracking_enabled: Option<bool>,
+    /// added to the ignored sensors of the scene
+    #[serde(default)]
+    pub ignored_sensors: Vec<String>,
+    #[serde(default)]
+    pub circadian: Option<bool>,
+}
+
+impl Overlay {
+    /// stack the overlay on top of a scene
+    pub fn apply(&self, scene: &mut Scene) {
+        if let Some(brightness) = self.brightness {
+            scene.brightness = brightness;
+        }
+        if let Some(color_temp) = self.color_temp {
+            scene.color_temp = Some(color_temp);
+        }
+        if let Some(color) = &self.color {
+            scene.color = Some(color.clone());
+        }
+        for (room, settings) in self.room_settings.iter() {
+            scene.room_settings.insert(room.clone(), settings.clone());
+        }
+        for (topic, settings) in self.switch_settings.iter() {
+            scene
+                .switch_settings
+                .insert(topic.clone(), settings.clone());
+        }
+        // a switch can only be in one of the lists
+        for topic in self.disabled_switches.iter() {
+            scene.enabled_switches.retain(|switch| switch != topic);
+            scene.ignored_switches.retain(|switch| switch != topic);
+            scene.disabled_switches.push(topic.clone());
+        }
+        for topic in self.enabled_switches.iter() {
+            scene.disabled_switches.retain(|switch| switch != topic);
+            scene.ignored_switches.retain(|switch| switch != topic);
+            scene.enabled_switches.push(topic.clone());
+        }
+        for topic in self.ignored_switches.iter() {
+            scene.disabled_switches.retain(|switch| switch != topic);
+            scene.enabled_switches.retain(|switch| switch != topic);
+            scene.ignored_switches.push(topic.clone());
+        }
+        if let Some(room_tracking_enabled) = self.room_tracking_enabled {
+            scene.room_tracking_enabled = room_tracking_enabled;
+        }
+        scene
+            .ignored_sensors
+            .extend(self.ignored_sensors.iter().cloned());
+        if let Some(circadian) = self.circadian {
+            scene.circadian = circadian;
+        }
+    }
+}
+
+/// Home Assistant mqtt discovery
+#[derive(Clone, Deserialize, Serialize)]
+pub struct HomeAssistant {
+    /// discovery prefix configured in Home Assistant
+    #[serde(default = "HomeAssistant::default_discovery_prefix")]
+    pub discovery_prefix: String,
+    /// node id, used for the discovery topics and unique ids
+    #[serde(default = "HomeAssistant::default_node_id")]
+    pub node_id: String,
+}
+
+impl HomeAssistant {
+    pub fn default_discovery_prefix() -> String {
+        "homeassistant".to_string()
+    }
+
+    pub fn default_node_id() -> String {
+        "light_control".to_string()
+    }
+}
+
+/// How sensors without any message are handled after start
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupPolicy {
+    /// sensors become absent after their delay
+    Wait,
+    /// sensors are absent right away
+    AssumeAbsent,
+}
+
+/// The available decision engines
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrategyKind {
+    /// track the current room of the occupants
+    RoomTracking,
+    /// only react on room presence, no current room tracking
+    Presence,
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -27,6 +355,72 @@ pub struct Credentials {
 }
 
 impl Configuration {
+    pub fn default_occupants() -> usize {
+        1
+    }
+
+    pub fn default_strategy() -> StrategyKind {
+        StrategyKind::RoomTracking
+    }
+
+    pub fn default_startup_policy() -> StartupPolicy {
+        StartupPolicy::Wait
+    }
+
+    pub fn default_control() -> Control {
+        Control {
+            qos: Control::default_qos(),
+            retain: Control::default_retain(),
+        }
+    }
+
+    /// all qos levels must be 0, 1 or 2
+    pub fn verify_qos(&self) -> Result<(), Box<dyn Error>> {
+        let mut qos_levels = vec![("control", self.control.qos)];
+        for sensor in self.sensors.iter() {
+            qos_levels.push((&sensor.topic, sensor.qos));
+        }
+        for switch in self.switches.iter() {
+            qos_levels.push((&switch.topic, switch.qos));
+            qos_levels.push((&switch.command.topic, switch.command.qos));
+        }
+        for trigger in self.triggers.iter() {
+            qos_levels.push((&trigger.topic, trigger.qos));
+        }
+        for (topic, qos) in qos_levels {
+            if !(0..=2).contains(&qos) {
+                error!("{}, has an invalid qos {} (0, 1 or 2)", topic, qos);
+                return Err(Box::new(ConfigurationError {}));
+            }
+        }
+        Ok(())
+    }
+
+    /// rates must be finite and above 0, to calculate when the next command can be sent
+    pub fn verify_rate_limit(&self) -> Result<(), Box<dyn Error>> {
+        let rate_limit = match &self.rate_limit {
+            Some(rate_limit) => rate_limit,
+            None => return Ok(()),
+        };
+        let mut rates = Vec::new();
+        if let Some(commands_per_second) = rate_limit.commands_per_second {
+            rates.push(("rate_limit", commands_per_second));
+        }
+        for prefix in rate_limit.prefixes.iter() {
+            rates.push((&prefix.prefix, prefix.commands_per_second));
+        }
+        for (name, commands_per_second) in rates {
+            if !commands_per_second.is_finite() || commands_per_second <= 0.0 {
+                error!(
+                    "{}, has an invalid commands_per_second {} (must be above 0)",
+                    name, commands_per_second
+                );
+                return Err(Box::new(ConfigurationError {}));
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_max_sensor_delay(&self) -> u64 {
         let mut result = 0;
         for sensor in self.sensors.iter() {
@@ -50,7 +444,10 @@ impl Configuration {
     pub fn load_from_file(path: &str) -> Result<Self, Box<dyn Error>> {
         let file = File::open(path)?;
         let reader = BufReader::new(file);
-        let configuration = serde_json::from_reader(reader)?;
+        let mut value: Value = serde_json::from_reader(reader)?;
+        Configuration::resolve_scene_extends(&mut value)?;
+        let mut configuration: Configuration = serde_json::from_value(value)?;
+        configuration.resolve_switch_references();
         return Ok(configuration);
     }
 
@@ -72,6 +469,104 @@ impl Configuration {
         return None;
     }
 
+    /// triggers which match a message
+    pub fn get_triggers_for_message(&self, topic: &str, payload: &Value) -> Vec<&Trigger> {
+        self.triggers
+            .iter()
+            .filter(|trigger| trigger.topic == topic && payload[&trigger.key] == trigger.value)
+            .collect()
+    }
+
+    /// all scenes used in triggers must exist
+    pub fn verify_triggers(&self) -> Result<(), Box<dyn Error>> {
+        for trigger in self.triggers.iter() {
+            let scenes = match &trigger.action {
+                TriggerAction::Scene(scene) => vec![scene.clone()],
+                TriggerAction::Cycle(scenes) => scenes.clone(),
+                _ => vec![],
+            };
+            for scene in scenes.iter() {
+                if self.get_scene(scene).is_none() {
+                    error!(
+                        "{}, is an unknown scene in trigger {}",
+                        scene, trigger.topic
+                    );
+                    return Err(Box::new(ConfigurationError {}));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_overlay(&self, name: &str) -> Option<&Overlay> {
+        self.overlays.iter().find(|overlay| overlay.name == name)
+    }
+
+    /// merge scenes which extend another scene with the extended scene,
+    /// so they only need to define the fields they change
+    pub fn resolve_scene_extends(configuration: &mut Value) -> Result<(), Box<dyn Error>> {
+        let scenes = match configuration["scenes"].as_array() {
+            Some(scenes) => scenes.clone(),
+            None => return Ok(()),
+        };
+        let mut resolved = Vec::new();
+        for scene in scenes.iter() {
+            resolved.push(Configuration::resolve_scene(scene, &scenes, 0)?);
+        }
+        configuration["scenes"] = Value::Array(resolved);
+        Ok(())
+    }
+
+    fn resolve_scene(
+        scene: &Value,
+        scenes: &[Value],
+        depth: usize,
+    ) -> Result<Value, Box<dyn Error>> {
+        let base_name = match scene["extends"].as_str() {
+            Some(base_name) => base_name,
+            None => return Ok(scene.clone()),
+        };
+        if depth >= scenes.len() {
+            error!("scene {}, extends itself", scene["name"]);
+            return Err(Box::new(ConfigurationError {}));
+        }
+        let base = match scenes
+            .iter()
+            .find(|base| base["name"].as_str() == Some(base_name))
+        {
+            Some(base) => base,
+            None => {
+                error!(
+                    "scene {}, extends unknown scene {}",
+                    scene["name"], base_name
+                );
+                return Err(Box::new(ConfigurationError {}));
+            }
+        };
+        let mut merged = Configuration::resolve_scene(base, scenes, depth + 1)?;
+        if let Some(fields) = scene.as_object() {
+            for (key, value) in fields.iter() {
+                match key.as_str() {
+                    "extends" => continue,
+                    // settings are merged per room and switch
+                    "room_settings" | "switch_settings" => {
+                        if let (Some(merged_settings), Some(settings)) =
+                            (merged[key.as_str()].as_object_mut(), value.as_object())
+                        {
+                            for (name, setting) in settings.iter() {
+                                merged_settings.insert(name.clone(), setting.clone());
+                            }
+                            continue;
+                        }
+                    }
+                    _ => {}
+                }
+                merged[key.as_str()] = value.clone();
+            }
+        }
+        Ok(merged)
+    }
+
     pub fn get_switch_for_topic(&self, topic: String) -> Option<&Switch> {
         for switch in self.switches.iter() {
             if switch.topic == topic {
@@ -81,6 +576,105 @@ impl Configuration {
         None
     }
 
+    /// topics of all switches a reference points to.
+    /// A reference is a switch topic, a switch name, a group or a tag.
+    pub fn resolve_switch_reference(&self, reference: &str) -> Vec<String> {
+        for switch in self.switches.iter() {
+            if switch.topic == reference || switch.name.as_deref() == Some(reference) {
+                return vec![switch.topic.clone()];
+            }
+        }
+        let mut topics = Vec::new();
+        // members of groups are switch topics, switch names or tags
+        let members = match self.groups.get(reference) {
+            Some(members) => members.iter().map(String::as_str).collect(),
+            None => vec![reference],
+        };
+        for member in members {
+            for switch in self.switches.iter() {
+                let matches = switch.topic == member
+                    || switch.name.as_deref() == Some(member)
+                    || switch.tags.iter().any(|tag| tag == member);
+                if matches && !topics.contains(&switch.topic) {
+                    topics.push(switch.topic.clone());
+                }
+            }
+        }
+        topics
+    }
+
+    fn resolve_switch_references_list(&self, scene: &str, references: &[String]) -> Vec<String> {
+        let mut topics = Vec::new();
+        for reference in references.iter() {
+            let mut resolved = self.resolve_switch_reference(reference);
+            if resolved.is_empty() {
+                warn!(
+                    "{} in scene {}, is no known switch, group or tag",
+                    reference, scene
+                );
+                resolved.push(reference.clone());
+            }
+            for topic in resolved {
+                if !topics.contains(&topic) {
+                    topics.push(topic);
+                }
+            }
+        }
+        topics
+    }
+
+    /// replace switch names, groups and tags in scenes by switch topics
+    pub fn resolve_switch_references(&mut self) {
+        let mut scenes = self.scenes.clone();
+        for scene in scenes.iter_mut() {
+            scene.disabled_switches =
+                self.resolve_switch_references_list(&scene.name, &scene.disabled_switches);
+            scene.enabled_switches =
+                self.resolve_switch_references_list(&scene.name, &scene.enabled_switches);
+            scene.ignored_switches =
+                self.resolve_switch_references_list(&scene.name, &scene.ignored_switches);
+            let mut switch_settings = HashMap::new();
+            for (reference, settings) in scene.switch_settings.iter() {
+                let references = vec![reference.clone()];
+                for topic in self.resolve_switch_references_list(&scene.name, &references) {
+                    switch_settings.insert(topic, settings.clone());
+                }
+            }
+            scene.switch_settings = switch_settings;
+        }
+        self.scenes = scenes;
+        let mut overlays = self.overlays.clone();
+        for overlay in overlays.iter_mut() {
+            overlay.disabled_switches =
+                self.resolve_switch_references_list(&overlay.name, &overlay.disabled_switches);
+            overlay.enabled_switches =
+                self.resolve_switch_references_list(&overlay.name, &overlay.enabled_switches);
+            overlay.ignored_switches =
+                self.resolve_switch_references_list(&overlay.name, &overlay.ignored_switches);
+            let mut switch_settings = HashMap::new();
+            for (reference, settings) in overlay.switch_settings.iter() {
+                let references = vec![reference.clone()];
+                for topic in self.resolve_switch_references_list(&overlay.name, &references) {
+                    switch_settings.insert(topic, settings.clone());
+                }
+            }
+            overlay.switch_settings = switch_settings;
+        }
+        self.overlays = overlays;
+    }
+
+    /// all rooms which contain sensors, sorted by name
+    pub fn get_rooms(&self) -> Vec<String> {
+        let mut rooms: Vec<String> = self
+            .sensors
+            .iter()
+            .map(|sensor| sensor.room.clone())
+            .collect();
+        rooms.sort();
+        rooms.dedup();
+        rooms
+    }
+
     pub fn get_topics(&self) -> Vec<&String> {
         let mut topics = Vec::new();
         for sensor in self.sensors.iter() {
@@ -92,6 +686,31 @@ impl Configuration {
         topics
     }
 
+    /// topics of all sensors and switches with the qos to subscribe,
+    /// topics used more than once are subscribed with the highest qos
+    pub fn get_subscriptions(&self) -> Vec<(String, i32)> {
+        let mut subscriptions: BTreeMap<String, i32> = BTreeMap::new();
+        let topics = self
+            .sensors
+            .iter()
+            .map(|sensor| (&sensor.topic, sensor.qos))
+            .chain(
+                self.switches
+                    .iter()
+                    .map(|switch| (&switch.topic, switch.qos)),
+            )
+            .chain(
+                self.triggers
+                    .iter()
+                    .map(|trigger| (&trigger.topic, trigger.qos)),
+            );
+        for (topic, qos) in topics {
+            let subscription = subscriptions.entry(topic.clone()).or_insert(qos);
+            *subscription = qos.max(*subscription);
+        }
+        subscriptions.into_iter().collect()
+    }
+
     pub fn get_update_sensor_for_topic(
         &self,
         topic: &str,
@@ -131,6 +750,27 @@ impl Configuration {
     }
 }
 
+/// Mqtt settings of the control topics
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Control {
+    /// qos to subscribe the set topic and to publish the state topics
+    #[serde(default = "Control::default_qos")]
+    pub qos: i32,
+    /// publish the state topics retained
+    #[serde(default = "Control::default_retain")]
+    pub retain: bool,
+}
+
+impl Control {
+    pub fn default_qos() -> i32 {
+        0
+    }
+
+    pub fn default_retain() -> bool {
+        true
+    }
+}
+
 /// A Sensor is a device that generates inputs
 /// like door open/close or motion detected undetected
 ///
@@ -154,6 +794,19 @@ pub struct Sensor {
     /// the absent message.
     #[serde(default = "Sensor::default_delay")]
     pub delay: u64,
+    /// kind of the sensor, which changes how states are interpreted
+    #[serde(default = "Sensor::default_kind")]
+    pub kind: SensorKind,
+    /// expected max time, in seconds, between two messages.
+    /// silent sensors are stale.
+    #[serde(default)]
+    pub heartbeat: Option<u64>,
+    /// stale sensors are not considered present
+    #[serde(default)]
+    pub ignore_when_stale: bool,
+    /// qos to subscribe the topic
+    #[serde(default)]
+    pub qos: i32,
 }
 
 impl Sensor {
@@ -163,6 +816,218 @@ impl Sensor {
     pub fn default_delay() -> u64 {
         60
     }
+    pub fn default_kind() -> SensorKind {
+        SensorKind::Motion
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorKind {
+    /// presents while motion is detected
+    Motion,
+    /// a door marks presents only briefly, because opening a door
+    /// is evidence of passing by, not of staying in the room.
+    Door,
+    /// a bed occupancy pad pins the room while present
+    Bed,
+    /// a chair pressure pad pins the room while present
+    Chair,
+}
+
+impl SensorKind {
+    /// transit sensors only mark presents for the delay of the sensor
+    /// and don't take part in the current room calculation.
+    pub fn is_transit(&self) -> bool {
+        matches!(self, SensorKind::Door)
+    }
+
+    /// sticky sensors make their room the current room while present
+    pub fn is_sticky(&self) -> bool {
+        matches!(self, SensorKind::Bed | SensorKind::Chair)
+    }
+}
+
+/// Brightness and color temperature following a curve over the day.
+/// Used by scenes which have circadian enabled.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Circadian {
+    /// points of the curve, values in between are interpolated
+    pub keyframes: Vec<Keyframe>,
+    /// how often, in seconds, switches which are on get updated
+    #[serde(default = "Circadian::default_update_interval")]
+    pub update_interval: u64,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Keyframe {
+    /// time of the day (HH:MM)
+    pub time: String,
+    pub brightness: u8,
+    #[serde(default)]
+    pub color_temp: Option<u16>,
+}
+
+impl Keyframe {
+    /// minute of the day, None if time is not valid
+    pub fn get_minute_of_day(&self) -> Option<u32> {
+        let mut parts = self.time.split(':');
+        let hour: u32 = parts.next()?.trim().parse().ok()?;
+        let minute: u32 = parts.next()?.trim().parse().ok()?;
+        if parts.next().is_some() || hour > 23 || minute > 59 {
+            return None;
+        }
+        Some(hour * 60 + minute)
+    }
+}
+
+impl Circadian {
+    pub fn default_update_interval() -> u64 {
+        300
+    }
+
+    /// verify if all keyframes are valid
+    pub fn verify(&self) -> Result<(), Box<dyn Error>> {
+        if self.keyframes.is_empty() {
+            error!("circadian needs at least one keyframe");
+            return Err(Box::new(ConfigurationError {}));
+        }
+        for keyframe in self.keyframes.iter() {
+            if keyframe.get_minute_of_day().is_none() {
+                error!(
+                    "{}, is not a valid time (HH:MM) in circadian",
+                    keyframe.time
+                );
+                return Err(Box::new(ConfigurationError {}));
+            }
+        }
+        Ok(())
+    }
+
+    /// brightness and color temperature for the given minute of the day,
+    /// interpolated between the surrounding keyframes.
+    pub fn get_settings(&self, minute_of_day: u32) -> Option<(u8, Option<u16>)> {
+        let mut keyframes: Vec<(u32, &Keyframe)> = self
+            .keyframes
+            .iter()
+            .filter_map(|keyframe| {
+                keyframe
+                    .get_minute_of_day()
+                    .map(|minute| (minute, keyframe))
+            })
+            .collect();
+        keyframes.sort_by_key(|(minute, _)| *minute);
+        let (first_minute, first) = *keyframes.first()?;
+        let (last_minute, last) = *keyframes.last()?;
+        // the curve wraps around midnight
+        let (from_minute, from, to_minute, to) =
+            if minute_of_day < first_minute || minute_of_day >= last_minute {
+                (last_minute, last, first_minute + 24 * 60, first)
+            } else {
+                let index = keyframes
+                    .iter()
+                    .rposition(|(minute, _)| *minute <= minute_of_day)?;
+                let (from_minute, from) = keyframes[index];
+                let (to_minute, to) = keyframes[index + 1];
+                (from_minute, from, to_minute, to)
+            };
+        let minute_of_day = if minute_of_day < from_minute {
+            minute_of_day + 24 * 60
+        } else {
+            minute_of_day
+        };
+        let progress = if to_minute == from_minute {
+            0.0
+        } else {
+            f64::from(minute_of_day - from_minute) / f64::from(to_minute - from_minute)
+        };
+        let interpolate = |from: f64, to: f64| -> f64 { (from + (to - from) * progress).round() };
+        let brightness = interpolate(f64::from(from.brightness), f64::from(to.brightness)) as u8;
+        let color_temp = match (from.color_temp, to.color_temp) {
+            (Some(from), Some(to)) => Some(interpolate(f64::from(from), f64::from(to)) as u16),
+            (color_temp, _) => color_temp,
+        };
+        Some((brightness, color_temp))
+    }
+}
+
+#[cfg(test)]
+mod circadian_tests {
+    use super::*;
+
+    fn create_keyframe(time: &str, brightness: u8, color_temp: u16) -> Keyframe {
+        Keyframe {
+            time: time.to_string(),
+            brightness,
+            color_temp: Some(color_temp),
+        }
+    }
+
+    fn create_circadian() -> Circadian {
+        Circadian {
+            keyframes: vec![
+                create_keyframe("20:00", 100, 400),
+                create_keyframe("08:00", 255, 250),
+                create_keyframe("12:00", 255, 200),
+            ],
+            update_interval: 300,
+        }
+    }
+
+    #[test]
+    fn test_get_minute_of_day() {
+        assert_eq!(
+            create_keyframe("08:30", 0, 0).get_minute_of_day(),
+            Some(510)
+        );
+        assert_eq!(create_keyframe("24:00", 0, 0).get_minute_of_day(), None);
+        assert_eq!(create_keyframe("8", 0, 0).get_minute_of_day(), None);
+    }
+
+    #[test]
+    fn test_get_settings_on_keyframe() {
+        let circadian = create_circadian();
+        assert_eq!(circadian.get_settings(8 * 60), Some((255, Some(250))));
+        assert_eq!(circadian.get_settings(20 * 60), Some((100, Some(400))));
+    }
+
+    #[test]
+    fn test_get_settings_interpolated() {
+        let circadian = create_circadian();
+        assert_eq!(circadian.get_settings(10 * 60), Some((255, Some(225))));
+        assert_eq!(circadian.get_settings(16 * 60), Some((178, Some(300))));
+    }
+
+    #[test]
+    fn test_get_settings_around_midnight() {
+        let circadian = create_circadian();
+        assert_eq!(circadian.get_settings(2 * 60), Some((178, Some(325))));
+        assert_eq!(circadian.get_settings(23 * 60), Some((139, Some(363))));
+    }
+}
+
+/// A Room describes which other rooms can be reached directly.
+///
+/// Rooms which are not listed are considered to be
+/// connected to every other room.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Room {
+    /// name of the room (as used by sensors and switches)
+    pub name: String,
+    /// rooms which can be reached directly from this room,
+    /// for example through a door or the floor.
+    #[serde(default)]
+    pub adjacent_rooms: Vec<String>,
+    /// how long, in seconds, this room must be present before it
+    /// takes over the current room from a room which is not adjacent.
+    #[serde(default = "Room::default_handover_delay")]
+    pub handover_delay: u64,
+}
+
+impl Room {
+    pub fn default_handover_delay() -> u64 {
+        30
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
@@ -210,6 +1075,12 @@ impl SensorState {
 pub struct Switch {
     /// uniq topic to listen for the switch
     pub topic: String,
+    /// human friendly name, which can be used instead of the topic in scenes
+    #[serde(default)]
+    pub name: Option<String>,
+    /// tags, which can be used to reference a set of switches in scenes
+    #[serde(default)]
+    pub tags: Vec<String>,
     /// key for state
     pub key: String,
     /// rooms this switch is placed
@@ -217,18 +1088,59 @@ pub struct Switch {
     pub rooms: Vec<String>,
     /// command control
     pub command: SwitchCommand,
+    /// switch can receive colors
+    #[serde(default)]
+    pub color_support: bool,
+    /// how long, in seconds, before the switch is turned off,
+    /// it is dimmed as a warning. 0 disables dimming.
+    #[serde(default = "Switch::default_dim_before_off")]
+    pub dim_before_off: u64,
+    /// brightness while dimmed before off
+    #[serde(default = "Switch::default_dim_brightness")]
+    pub dim_brightness: u8,
+    /// transition time in seconds, handed to the command template
+    #[serde(default)]
+    pub transition: Option<u64>,
     /// how long to wait, in seconds, till the switch is turned off
     /// once it's room becomes the absent state.
     #[serde(default = "Switch::default_delay")]
     pub delay: u64,
+    /// how long, in seconds, the switch has to confirm a command
+    /// on its state topic, before the command is sent again.
+    /// None disables confirmation.
+    #[serde(default)]
+    pub confirm_timeout: Option<u64>,
+    /// how often an unconfirmed command is sent again,
+    /// before the switch is flagged unresponsive
+    #[serde(default = "Switch::default_max_retries")]
+    pub max_retries: u32,
+    /// qos to subscribe the topic
+    #[serde(default)]
+    pub qos: i32,
 }
 
 impl Switch {
     pub fn default_delay() -> u64 {
         0
     }
-    pub fn get_topic_and_command(&self, state: SwitchState, brightness: u8) -> (&String, String) {
-        self.command.get_topic_and_command(state, brightness)
+    pub fn default_dim_before_off() -> u64 {
+        0
+    }
+    pub fn default_dim_brightness() -> u8 {
+        25
+    }
+    pub fn default_max_retries() -> u32 {
+        3
+    }
+    pub fn get_topic_and_command(
+        &self,
+        state: SwitchState,
+        brightness: u8,
+        color_temp: Option<u16>,
+        color: Option<&Color>,
+    ) -> (&String, String) {
+        self.command
+            .get_topic_and_command(state, brightness, color_temp, color, self.transition)
     }
 }
 
@@ -238,8 +1150,10 @@ pub struct SwitchCommand {
     /// This is a mustache template. The arguments given are
     /// * state : on/off (see on off statement)
     /// * brightness : 0 - 255
-    /// * rgb (todo)
-    /// * warmth (todo)
+    /// * color_temp : color temperature (only if the scene defines one)
+    /// * rgb.r, rgb.g, rgb.b, hex, hue, saturation : color
+    ///   (only if the switch supports colors and the scene defines one)
+    /// * transition : transition time in seconds (only if the switch defines one)
     pub command: String,
     /// command to get state of the device
     /// useful at program start.
@@ -253,9 +1167,15 @@ pub struct SwitchCommand {
     /// string to send for state argument to run switch off
     #[serde(default = "SwitchCommand::default_off")]
     pub off: String,
+    /// qos to publish commands
+    #[serde(default)]
+    pub qos: i32,
+    /// publish commands retained
+    #[serde(default)]
+    pub retain: bool,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize, Serialize)]
 pub enum SwitchState {
     Unknown,
     On,
@@ -293,19 +1213,44 @@ impl SwitchCommand {
     pub fn default_off() -> String {
         "OFF".to_string()
     }
-    pub fn get_topic_and_command(&self, state: SwitchState, brightness: u8) -> (&String, String) {
+    pub fn get_topic_and_command(
+        &self,
+        state: SwitchState,
+        brightness: u8,
+        color_temp: Option<u16>,
+        color: Option<&Color>,
+        transition: Option<u64>,
+    ) -> (&String, String) {
         debug_assert_ne!(state, SwitchState::Unknown);
         let state_value = match state {
             SwitchState::On => &self.on,
             SwitchState::Off => &self.off,
             SwitchState::Unknown => &self.off, // should never happen
         };
-        let data = MapBuilder::new()
+        let mut data = MapBuilder::new()
             .insert("state", state_value)
             .unwrap()
             .insert("brightness", &brightness.to_string())
-            .unwrap()
-            .build();
+            .unwrap();
+        if let Some(color_temp) = color_temp {
+            data = data.insert("color_temp", &color_temp.to_string()).unwrap();
+        }
+        if let Some(transition) = transition {
+            data = data.insert("transition", &transition.to_string()).unwrap();
+        }
+        if let Some((r, g, b)) = color.and_then(|color| color.to_rgb()) {
+            let (hue, saturation) = Color::rgb_to_hue_saturation(r, g, b);
+            data = data
+                .insert_map("rgb", |rgb| {
+                    rgb.insert_str("r", r.to_string())
+                        .insert_str("g", g.to_string())
+                        .insert_str("b", b.to_string())
+                })
+                .insert_str("hex", format!("#{:02x}{:02x}{:02x}", r, g, b))
+                .insert_str("hue", hue.to_string())
+                .insert_str("saturation", saturation.to_string());
+        }
+        let data = data.build();
         let topic = &self.topic;
         let template = mustache::compile_str(&self.command).expect("couldn't create template ");
         let command = template.render_data_to_string(&data).unwrap();
@@ -325,11 +1270,15 @@ mod switch_tests {
             topic: "test/test/test".to_string(),
             on: "1".to_string(),
             off: "0".to_string(),
+            qos: 0,
+            retain: false,
         };
-        let (topic, command) = switch_command.get_topic_and_command(SwitchState::On, 123);
+        let (topic, command) =
+            switch_command.get_topic_and_command(SwitchState::On, 123, None, None, None);
         assert_eq!(topic, "test/test/test");
         assert_eq!(command, r#"{"test":1}"#);
-        let (topic, command) = switch_command.get_topic_and_command(SwitchState::Off, 123);
+        let (topic, command) =
+            switch_command.get_topic_and_command(SwitchState::Off, 123, None, None, None);
         assert_eq!(topic, "test/test/test");
         assert_eq!(command, r#"{"test":0}"#);
     }
@@ -342,14 +1291,71 @@ mod switch_tests {
             topic: "lights/light_1/set".to_string(),
             on: "ON".to_string(),
             off: "OFF".to_string(),
+            qos: 0,
+            retain: false,
         };
-        let (topic, command) = switch_command.get_topic_and_command(SwitchState::On, 123);
+        let (topic, command) =
+            switch_command.get_topic_and_command(SwitchState::On, 123, None, None, None);
         assert_eq!(topic, "lights/light_1/set");
         assert_eq!(command, r#"{"state":"ON","brightness":123}"#);
-        let (topic, command) = switch_command.get_topic_and_command(SwitchState::Off, 123);
+        let (topic, command) =
+            switch_command.get_topic_and_command(SwitchState::Off, 123, None, None, None);
         assert_eq!(topic, "lights/light_1/set");
         assert_eq!(command, r#"{"state":"OFF","brightness":123}"#);
     }
+
+    #[test]
+    fn test_get_topic_and_command_color_temp() {
+        let switch_command = SwitchCommand {
+            command: r#"{"state":"{{state}}","color_temp":{{color_temp}}}"#.to_string(),
+            init_command: None,
+            topic: "lights/light_1/set".to_string(),
+            on: "ON".to_string(),
+            off: "OFF".to_string(),
+            qos: 0,
+            retain: false,
+        };
+        let (_, command) =
+            switch_command.get_topic_and_command(SwitchState::On, 123, Some(370), None, None);
+        assert_eq!(command, r#"{"state":"ON","color_temp":370}"#);
+    }
+
+    #[test]
+    fn test_get_topic_and_command_transition() {
+        let switch_command = SwitchCommand {
+            command: r#"{"state":"{{state}}","transition":{{transition}}}"#.to_string(),
+            init_command: None,
+            topic: "lights/light_1/set".to_string(),
+            on: "ON".to_string(),
+            off: "OFF".to_string(),
+            qos: 0,
+            retain: false,
+        };
+        let (_, command) =
+            switch_command.get_topic_and_command(SwitchState::On, 123, None, None, Some(2));
+        assert_eq!(command, r#"{"state":"ON","transition":2}"#);
+    }
+
+    #[test]
+    fn test_get_topic_and_command_color() {
+        let switch_command = SwitchCommand {
+            command: r#"{"rgb":[{{rgb.r}},{{rgb.g}},{{rgb.b}}],"hex":"{{hex}}","hs":[{{hue}},{{saturation}}]}"#
+                .to_string(),
+            init_command: None,
+            topic: "lights/light_1/set".to_string(),
+            on: "ON".to_string(),
+            off: "OFF".to_string(),
+            qos: 0,
+            retain: false,
+        };
+        let color = Color::Hex("#ff0000".to_string());
+        let (_, command) =
+            switch_command.get_topic_and_command(SwitchState::On, 123, None, Some(&color), None);
+        assert_eq!(
+            command,
+            r##"{"rgb":[255,0,0],"hex":"#ff0000","hs":[0,100]}"##
+        );
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -359,6 +1365,19 @@ pub struct Scene {
     /// brightness level of the scene
     #[serde(default = "Scene::default_brightness")]
     pub brightness: u8,
+    /// color temperature of the scene
+    #[serde(default)]
+    pub color_temp: Option<u16>,
+    /// color of the scene, only send to switches which support colors
+    #[serde(default)]
+    pub color: Option<Color>,
+    /// brightness and color temperature per room
+    #[serde(default)]
+    pub room_settings: HashMap<String, LightSettings>,
+    /// brightness and color temperature per switch topic,
+    /// these win over room_settings
+    #[serde(default)]
+    pub switch_settings: HashMap<String, LightSettings>,
     /// list all switch topics which should not turned on anymore.
     /// they will be turned off by entering this scene
     #[serde(default)]
@@ -373,6 +1392,122 @@ pub struct Scene {
     /// ignore these sensors
     #[serde(default)]
     pub ignored_sensors: Vec<String>,
+    /// brightness and color temperature follow the circadian curve
+    #[serde(default)]
+    pub circadian: bool,
+}
+
+/// overrides the brightness and color temperature of a scene
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct LightSettings {
+    #[serde(default)]
+    pub brightness: Option<u8>,
+    #[serde(default)]
+    pub color_temp: Option<u16>,
+    #[serde(default)]
+    pub color: Option<Color>,
+}
+
+/// A color given as hex string (`"#ff0000"`),
+/// rgb triplet (`{"r":255,"g":0,"b":0}`) or
+/// hue (0 - 360) and saturation (0 - 100) (`{"hue":0,"saturation":100}`).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Color {
+    Hex(String),
+    Rgb { r: u8, g: u8, b: u8 },
+    HueSaturation { hue: u16, saturation: u8 },
+}
+
+impl Color {
+    /// None if the hex string is not valid
+    pub fn to_rgb(&self) -> Option<(u8, u8, u8)> {
+        match self {
+            Color::Hex(hex) => {
+                let hex = hex.trim_start_matches('#');
+                if hex.len() != 6 {
+                    return None;
+                }
+                let r = u8::from_str_radix(hex.get(0..2)?, 16).ok()?;
+                let g = u8::from_str_radix(hex.get(2..4)?, 16).ok()?;
+                let b = u8::from_str_radix(hex.get(4..6)?, 16).ok()?;
+                Some((r, g, b))
+            }
+            Color::Rgb { r, g, b } => Some((*r, *g, *b)),
+            Color::HueSaturation { hue, saturation } => {
+                Some(Color::hue_saturation_to_rgb(*hue, *saturation))
+            }
+        }
+    }
+
+    /// hue (0 - 360) and saturation (0 - 100) of full brightness
+    pub fn hue_saturation_to_rgb(hue: u16, saturation: u8) -> (u8, u8, u8) {
+        let hue = f64::from(hue % 360) / 60.0;
+        let chroma = f64::from(saturation.min(100)) / 100.0;
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (r, g, b) = match hue as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let offset = 1.0 - chroma;
+        let to_u8 = |value: f64| ((value + offset) * 255.0).round() as u8;
+        (to_u8(r), to_u8(g), to_u8(b))
+    }
+
+    /// hue (0 - 360) and saturation (0 - 100)
+    pub fn rgb_to_hue_saturation(r: u8, g: u8, b: u8) -> (u16, u8) {
+        let (r, g, b) = (f64::from(r), f64::from(g), f64::from(b));
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        if delta == 0.0 {
+            return (0, 0);
+        }
+        let hue = if max == r {
+            60.0 * (((g - b) / delta) % 6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        let hue = if hue < 0.0 { hue + 360.0 } else { hue };
+        let saturation = delta / max * 100.0;
+        (hue.round() as u16 % 360, saturation.round() as u8)
+    }
+}
+
+#[cfg(test)]
+mod color_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_colors() {
+        let colors: Vec<Color> = serde_json::from_str(
+            r##"["#00ff00", {"r":0,"g":255,"b":0}, {"hue":120,"saturation":100}]"##,
+        )
+        .unwrap();
+        for color in colors.iter() {
+            assert_eq!(color.to_rgb(), Some((0, 255, 0)));
+        }
+    }
+
+    #[test]
+    fn test_invalid_hex() {
+        assert_eq!(Color::Hex("#00ff0".to_string()).to_rgb(), None);
+        assert_eq!(Color::Hex("#00ffzz".to_string()).to_rgb(), None);
+    }
+
+    #[test]
+    fn test_rgb_to_hue_saturation() {
+        assert_eq!(Color::rgb_to_hue_saturation(255, 0, 0), (0, 100));
+        assert_eq!(Color::rgb_to_hue_saturation(0, 0, 255), (240, 100));
+        assert_eq!(Color::rgb_to_hue_saturation(255, 128, 128), (0, 50));
+        assert_eq!(Color::rgb_to_hue_saturation(80, 80, 80), (0, 0));
+    }
 }
 
 impl Scene {
@@ -385,6 +1520,21 @@ impl Scene {
 
     /// verify if scene is consistent
     pub fn verify(&self) -> Result<(), Box<dyn Error>> {
+        let colors = self
+            .color
+            .iter()
+            .chain(self.room_settings.values().filter_map(|s| s.color.as_ref()))
+            .chain(
+                self.switch_settings
+                    .values()
+                    .filter_map(|s| s.color.as_ref()),
+            );
+        for color in colors {
+            if color.to_rgb().is_none() {
+                error!("{:?}, is not a valid color in {}", color, self.name);
+                return Err(Box::new(ConfigurationError {}));
+            }
+        }
         for disabled_switch in self.disabled_switches.iter() {
             if self.enabled_switches.contains(&disabled_switch) {
                 error!(
@@ -437,6 +1587,176 @@ impl Scene {
     }
 }
 
+#[cfg(test)]
+mod configuration_tests {
+    use super::*;
+    use crate::dummy_configuration::hardcoded_config;
+
+    #[test]
+    fn test_get_subscriptions() {
+        let mut configuration = hardcoded_config();
+        configuration.sensors[4].qos = 1;
+        let subscriptions = configuration.get_subscriptions();
+        // motion_sensor_5 is used in two rooms
+        assert_eq!(subscriptions.len(), 17);
+        assert!(subscriptions.contains(&("zigbee2mqtt/motion_sensor_5".to_string(), 1)));
+        assert!(subscriptions.contains(&("stat/PAL01/RESULT".to_string(), 0)));
+    }
+
+    #[test]
+    fn test_resolve_switch_references() {
+        let mut configuration = hardcoded_config();
+        configuration.switches[0].name = Some("floor".to_string());
+        configuration.switches[4].tags = vec!["ceiling".to_string()];
+        configuration.switches[5].tags = vec!["ceiling".to_string()];
+        configuration.groups.insert(
+            "bedroom_all".to_string(),
+            vec!["ceiling".to_string(), "stat/PAL04/RESULT".to_string()],
+        );
+        configuration.scenes[1].disabled_switches = vec![
+            "floor".to_string(),
+            "bedroom_all".to_string(),
+            "zigbee2mqtt/unknown".to_string(),
+        ];
+        configuration.scenes[1].enabled_switches = vec!["ceiling".to_string()];
+        configuration.resolve_switch_references();
+        assert_eq!(
+            configuration.scenes[1].disabled_switches,
+            vec![
+                "zigbee2mqtt/light_1".to_string(),
+                "zigbee2mqtt/light_8".to_string(),
+                "stat/PAL01/RESULT".to_string(),
+                "stat/PAL04/RESULT".to_string(),
+                "zigbee2mqtt/unknown".to_string(),
+            ]
+        );
+        assert_eq!(
+            configuration.scenes[1].enabled_switches,
+            vec![
+                "zigbee2mqtt/light_8".to_string(),
+                "stat/PAL01/RESULT".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_scene_extends() {
+        let mut value: Value = serde_json::from_str(
+            r#"{"scenes":[
+                {"name":"day","brightness":255,"room_settings":{"bed_room":{"brightness":100}}},
+                {"name":"evening","extends":"day","room_settings":{"kitchen":{"brightness":50}}},
+                {"name":"night","extends":"evening","brightness":25}
+            ]}"#,
+        )
+        .unwrap();
+        Configuration::resolve_scene_extends(&mut value).unwrap();
+        let night = &value["scenes"][2];
+        assert_eq!(night["brightness"], 25);
+        assert_eq!(night["room_settings"]["bed_room"]["brightness"], 100);
+        assert_eq!(night["room_settings"]["kitchen"]["brightness"], 50);
+        assert!(night.get("extends").is_none());
+
+        let mut value: Value = serde_json::from_str(
+            r#"{"scenes":[
+                {"name":"day","extends":"night"},
+                {"name":"night","extends":"day"}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(Configuration::resolve_scene_extends(&mut value).is_err());
+    }
+
+    #[test]
+    fn test_overlay_apply() {
+        let mut configuration = hardcoded_config();
+        let overlay = Overlay {
+            name: "movie".to_string(),
+            brightness: Some(40),
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
+            disabled_switches: vec![],
+            enabled_switches: vec!["stat/PAL03/RESULT".to_string()],
+            ignored_switches: vec![],
+            room_tracking_enabled: None,
+            ignored_sensors: vec![],
+            circadian: None,
+        };
+        let scene = &mut configuration.scenes[1];
+        overlay.apply(scene);
+        assert_eq!(scene.brightness, 40);
+        assert!(!scene.room_tracking_enabled);
+        assert!(scene.verify().is_ok());
+        assert!(scene
+            .enabled_switches
+            .contains(&"stat/PAL03/RESULT".to_string()));
+        assert!(!scene
+            .disabled_switches
+            .contains(&"stat/PAL03/RESULT".to_string()));
+    }
+
+    #[test]
+    fn test_get_triggers_for_message() {
+        let mut configuration = hardcoded_config();
+        configuration.triggers = serde_json::from_str(
+            r#"[
+                {"topic":"zigbee2mqtt/button_1","key":"action","value":"single","action":{"scene":"night"}},
+                {"topic":"zigbee2mqtt/button_1","key":"action","value":"double","action":{"cycle":["default","night"]}},
+                {"topic":"zigbee2mqtt/button_1","key":"action","value":"long","action":"toggle_room_tracking"}
+            ]"#,
+        )
+        .unwrap();
+        assert!(configuration.verify_triggers().is_ok());
+        let payload = serde_json::from_str(r#"{"action":"double","battery":100}"#).unwrap();
+        let triggers = configuration.get_triggers_for_message("zigbee2mqtt/button_1", &payload);
+        assert_eq!(triggers.len(), 1);
+        assert_eq!(
+            triggers[0].action,
+            TriggerAction::Cycle(vec!["default".to_string(), "night".to_string()])
+        );
+        assert!(configuration
+            .get_triggers_for_message("zigbee2mqtt/button_2", &payload)
+            .is_empty());
+        assert!(configuration
+            .get_subscriptions()
+            .contains(&("zigbee2mqtt/button_1".to_string(), 0)));
+
+        configuration.triggers[0].action = TriggerAction::Scene("party".to_string());
+        assert!(configuration.verify_triggers().is_err());
+    }
+
+    #[test]
+    fn test_verify_qos() {
+        let mut configuration = hardcoded_config();
+        assert!(configuration.verify_qos().is_ok());
+        configuration.switches[0].command.qos = 3;
+        assert!(configuration.verify_qos().is_err());
+    }
+
+    #[test]
+    fn test_verify_rate_limit() {
+        let mut configuration = hardcoded_config();
+        configuration.rate_limit = Some(RateLimit {
+            commands_per_second: Some(20.0),
+            prefixes: vec![PrefixRateLimit {
+                prefix: "zigbee2mqtt/".to_string(),
+                commands_per_second: 5.0,
+            }],
+        });
+        assert!(configuration.verify_rate_limit().is_ok());
+        configuration.rate_limit.as_mut().unwrap().prefixes[0].commands_per_second = 0.0;
+        assert!(configuration.verify_rate_limit().is_err());
+        configuration.rate_limit.as_mut().unwrap().prefixes.clear();
+        configuration
+            .rate_limit
+            .as_mut()
+            .unwrap()
+            .commands_per_second = Some(-1.0);
+        assert!(configuration.verify_rate_limit().is_err());
+    }
+}
+
 #[cfg(test)]
 mod test_scene {
     use super::*;
@@ -446,11 +1766,16 @@ mod test_scene {
         let scene = Scene {
             name: "".to_string(),
             brightness: 0,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
             disabled_switches: vec!["test1".to_string()],
             enabled_switches: vec!["test2".to_string()],
             ignored_switches: vec!["test3".to_string()],
             room_tracking_enabled: false,
             ignored_sensors: vec![],
+            circadian: false,
         };
         match scene.verify() {
             Err(_) => panic!("verification failed but it shouldn't"),
@@ -463,11 +1788,16 @@ mod test_scene {
         let scene = Scene {
             name: "".to_string(),
             brightness: 0,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
             disabled_switches: vec!["test1".to_string()],
             enabled_switches: vec!["test1".to_string()],
             ignored_switches: vec!["test3".to_string()],
             room_tracking_enabled: false,
             ignored_sensors: vec![],
+            circadian: false,
         };
         match scene.verify() {
             Ok(_) => panic!("verification successful but it shouldn't"),
@@ -480,11 +1810,16 @@ mod test_scene {
         let scene = Scene {
             name: "".to_string(),
             brightness: 0,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
             disabled_switches: vec!["test1".to_string()],
             enabled_switches: vec!["test2".to_string()],
             ignored_switches: vec!["test2".to_string()],
             room_tracking_enabled: false,
             ignored_sensors: vec![],
+            circadian: false,
         };
         match scene.verify() {
             Ok(_) => panic!("verification successful but it shouldn't"),
@@ -497,11 +1832,16 @@ mod test_scene {
         let scene = Scene {
             name: "".to_string(),
             brightness: 0,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
             disabled_switches: vec!["test1".to_string()],
             enabled_switches: vec!["test2".to_string()],
             ignored_switches: vec!["test1".to_string()],
             room_tracking_enabled: false,
             ignored_sensors: vec![],
+            circadian: false,
         };
         match scene.verify() {
             Ok(_) => panic!("verification successful but it shouldn't"),
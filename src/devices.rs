@@ -0,0 +1,146 @@
+//! battery and link quality monitoring of the configured devices
+
+use crate::configuration::{Configuration, DeviceMonitoring};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Last known battery level and link quality of a device
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DeviceStatus {
+    pub battery: Option<u64>,
+    pub linkquality: Option<u64>,
+    /// battery level is below the threshold
+    pub low_battery: bool,
+    /// link quality is below the threshold
+    pub weak_link: bool,
+}
+
+/// Reads battery level and link quality of all sensors and switches
+pub struct DeviceMonitor {
+    monitoring: DeviceMonitoring,
+    /// status by topic of all configured devices
+    devices: BTreeMap<String, DeviceStatus>,
+}
+
+impl DeviceMonitor {
+    pub fn new(configuration: &Configuration, monitoring: DeviceMonitoring) -> Self {
+        let mut devices = BTreeMap::new();
+        for topic in configuration.get_topics() {
+            devices.insert(topic.clone(), DeviceStatus::default());
+        }
+        DeviceMonitor {
+            monitoring,
+            devices,
+        }
+    }
+
+    /// read battery level and link quality of a message.
+    /// messages of unknown topics are ignored
+    pub fn update(&mut self, topic: &str, payload: &Value) {
+        let device = match self.devices.get_mut(topic) {
+            Some(device) => device,
+            None => return,
+        };
+        if let Some(battery) = DeviceMonitor::read_number(&payload[&self.monitoring.battery_key]) {
+            device.battery = Some(battery);
+            let low_battery = battery < self.monitoring.battery_threshold;
+            if low_battery && !device.low_battery {
+                warn!("{} has a low battery : {}", topic, battery);
+            }
+            device.low_battery = low_battery;
+        }
+        if let Some(linkquality) =
+            DeviceMonitor::read_number(&payload[&self.monitoring.linkquality_key])
+        {
+            device.linkquality = Some(linkquality);
+            let weak_link = linkquality < self.monitoring.linkquality_threshold;
+            if weak_link && !device.weak_link {
+                warn!("{} has a weak link : {}", topic, linkquality);
+            }
+            device.weak_link = weak_link;
+        }
+    }
+
+    fn read_number(value: &Value) -> Option<u64> {
+        value.as_f64().map(|value| value.max(0.0).round() as u64)
+    }
+
+    pub fn get_devices(&self) -> &BTreeMap<String, DeviceStatus> {
+        &self.devices
+    }
+
+    /// topics of devices with a low battery
+    pub fn get_low_battery(&self) -> Vec<String> {
+        self.devices
+            .iter()
+            .filter(|(_, device)| device.low_battery)
+            .map(|(topic, _)| topic.clone())
+            .collect()
+    }
+
+    /// topics of devices with a weak link
+    pub fn get_weak_link(&self) -> Vec<String> {
+        self.devices
+            .iter()
+            .filter(|(_, device)| device.weak_link)
+            .map(|(topic, _)| topic.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy_configuration::hardcoded_config;
+
+    fn create_monitor() -> DeviceMonitor {
+        let monitoring = DeviceMonitoring {
+            battery_key: DeviceMonitoring::default_battery_key(),
+            linkquality_key: DeviceMonitoring::default_linkquality_key(),
+            battery_threshold: DeviceMonitoring::default_battery_threshold(),
+            linkquality_threshold: DeviceMonitoring::default_linkquality_threshold(),
+        };
+        DeviceMonitor::new(&hardcoded_config(), monitoring)
+    }
+
+    #[test]
+    fn test_update() {
+        let mut monitor = create_monitor();
+        let payload =
+            serde_json::from_str(r#"{"battery":15,"linkquality":120,"occupancy":true}"#).unwrap();
+        monitor.update("zigbee2mqtt/motion_sensor_2", &payload);
+        assert_eq!(
+            monitor.get_devices().get("zigbee2mqtt/motion_sensor_2"),
+            Some(&DeviceStatus {
+                battery: Some(15),
+                linkquality: Some(120),
+                low_battery: true,
+                weak_link: false,
+            })
+        );
+        assert_eq!(
+            monitor.get_low_battery(),
+            vec!["zigbee2mqtt/motion_sensor_2".to_string()]
+        );
+        assert!(monitor.get_weak_link().is_empty());
+
+        // values which are not part of the message are kept
+        let payload = serde_json::from_str(r#"{"battery":90}"#).unwrap();
+        monitor.update("zigbee2mqtt/motion_sensor_2", &payload);
+        let device = monitor
+            .get_devices()
+            .get("zigbee2mqtt/motion_sensor_2")
+            .unwrap();
+        assert_eq!(device.linkquality, Some(120));
+        assert!(!device.low_battery);
+    }
+
+    #[test]
+    fn test_update_unknown_topic() {
+        let mut monitor = create_monitor();
+        let payload = serde_json::from_str(r#"{"battery":15}"#).unwrap();
+        monitor.update("zigbee2mqtt/unknown", &payload);
+        assert!(monitor.get_devices().get("zigbee2mqtt/unknown").is_none());
+    }
+}
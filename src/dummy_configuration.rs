@@ -1,4 +1,8 @@
-use crate::configuration::{Configuration, Credentials, Scene, Sensor, Switch, SwitchCommand};
+use crate::configuration::{
+    Configuration, Credentials, Scene, Sensor, SensorKind, StartupPolicy, StrategyKind, Switch,
+    SwitchCommand,
+};
+use std::collections::HashMap;
 
 #[allow(dead_code)]
 pub fn hardcoded_config() -> Configuration {
@@ -31,15 +35,24 @@ pub fn hardcoded_config() -> Configuration {
             room_tracking_enabled: true,
             name: "default".to_string(),
             brightness: 255,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
             enabled_switches: vec![],
             ignored_switches: vec![],
             disabled_switches: vec![],
             ignored_sensors: vec![],
+            circadian: false,
         },
         Scene {
             room_tracking_enabled: false,
             name: "night".to_string(),
             brightness: 25,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
             enabled_switches: vec![],
             ignored_switches: vec![],
             disabled_switches: vec![
@@ -49,6 +62,7 @@ pub fn hardcoded_config() -> Configuration {
                 "zigbee2mqtt/light_2".to_string(),
             ],
             ignored_sensors: vec![],
+            circadian: false,
         },
     ];
 
@@ -59,8 +73,23 @@ pub fn hardcoded_config() -> Configuration {
             password: "hallo".to_string(),
         },
         switches,
+        groups: HashMap::new(),
+        overlays: vec![],
+        triggers: vec![],
         sensors,
         scenes,
+        rooms: vec![],
+        occupants: 1,
+        circadian: None,
+        strategy: StrategyKind::RoomTracking,
+        startup_policy: StartupPolicy::Wait,
+        homeassistant: None,
+        http: None,
+        control: Configuration::default_control(),
+        device_monitoring: None,
+        rate_limit: None,
+        away: None,
+        vacation: None,
     }
 }
 
@@ -72,6 +101,10 @@ pub fn create_motion_sensor(topic: &str, rooms: String) -> Sensor {
         invert_state: false,
         delay: 60,
         room: rooms,
+        kind: SensorKind::Motion,
+        heartbeat: None,
+        ignore_when_stale: false,
+        qos: 0,
     }
 }
 
@@ -83,6 +116,10 @@ pub fn create_door_sensor(topic: &str, rooms: String) -> Sensor {
         invert_state: true,
         delay: 120,
         room: rooms,
+        kind: SensorKind::Door,
+        heartbeat: None,
+        ignore_when_stale: false,
+        qos: 0,
     }
 }
 
@@ -93,6 +130,15 @@ pub fn create_light_switch(name: &str, rooms: Vec<String>) -> Switch {
         rooms: rooms,
         key: "state".to_string(),
         delay: 0,
+        color_support: false,
+        dim_before_off: 0,
+        dim_brightness: 25,
+        transition: None,
+        confirm_timeout: None,
+        max_retries: 3,
+        qos: 0,
+        name: None,
+        tags: vec![],
         //state: SwitchState::Off,
         command: SwitchCommand {
             topic: format!("zigbee2mqtt/{}/set", name),
@@ -100,6 +146,8 @@ pub fn create_light_switch(name: &str, rooms: Vec<String>) -> Switch {
             init_command: None,
             on: "ON".to_string(),
             off: "OFF".to_string(),
+            qos: 0,
+            retain: false,
         },
     }
 }
@@ -111,6 +159,15 @@ pub fn create_sonoff_switch(name: &str, rooms: Vec<String>) -> Switch {
         rooms: rooms,
         key: "POWER".to_string(),
         delay: 0,
+        color_support: false,
+        dim_before_off: 0,
+        dim_brightness: 25,
+        transition: None,
+        confirm_timeout: None,
+        max_retries: 3,
+        qos: 0,
+        name: None,
+        tags: vec![],
         //state: SwitchState::Off,
         command: SwitchCommand {
             topic: format!("cmnd/{}/POWER", name),
@@ -118,6 +175,8 @@ pub fn create_sonoff_switch(name: &str, rooms: Vec<String>) -> Switch {
             init_command: Some("(null)".to_string()),
             on: "ON".to_string(),
             off: "OFF".to_string(),
+            qos: 0,
+            retain: false,
         },
     }
 }
@@ -0,0 +1,63 @@
+use crate::configuration::{Configuration, StrategyKind, SwitchState};
+use crate::presence::PresenceStrategy;
+use crate::snapshot::Snapshot;
+use crate::strategy::{Strategy, SwitchCommand};
+use crate::UpdateMessage;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// The decision engine gets all updates and
+/// decides which switch commands should be send.
+pub trait DecisionEngine {
+    /// handle an update and return the switch commands to send
+    fn handle(&mut self, message: UpdateMessage) -> Vec<SwitchCommand>;
+
+    /// the next instant at which a `Ping` should be send,
+    /// None if nothing changes without new updates
+    fn next_deadline(&self) -> Option<Instant>;
+
+    /// snapshot of the runtime state
+    fn snapshot(&self) -> Snapshot;
+
+    /// restore the runtime state of a snapshot
+    fn restore(&mut self, snapshot: &Snapshot, configuration: &Configuration);
+
+    /// current status, to show it to the user
+    fn status(&self) -> EngineStatus;
+}
+
+/// Status of the decision engine
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EngineStatus {
+    /// name of the active scene
+    pub scene: Option<String>,
+    /// names of the overlays stacked on top of the scene
+    pub overlays: Vec<String>,
+    pub current_room: Option<String>,
+    /// presence per room
+    pub rooms: BTreeMap<String, bool>,
+    pub brightness: u8,
+    pub room_tracking: bool,
+    /// last known state per switch
+    pub switches: BTreeMap<String, SwitchState>,
+    /// manual overrides per switch
+    pub switch_overrides: BTreeMap<String, SwitchState>,
+    /// sensors which didn't send a message for longer than their heartbeat
+    pub stale_sensors: Vec<String>,
+    /// switches which didn't confirm a command after all retries
+    pub unresponsive_switches: Vec<String>,
+    /// the whole home is absent
+    pub away: bool,
+    /// presence is simulated while on vacation
+    pub vacation: bool,
+}
+
+/// create the decision engine chosen in the configuration
+pub fn create_engine(configuration: &Configuration) -> Box<dyn DecisionEngine> {
+    info!("strategy: {:?}", configuration.strategy);
+    match configuration.strategy {
+        StrategyKind::RoomTracking => Box::new(Strategy::new(configuration)),
+        StrategyKind::Presence => Box::new(PresenceStrategy::new(configuration)),
+    }
+}
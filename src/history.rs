@@ -0,0 +1,233 @@
+//! recorded room presence, replayed to simulate presence while on vacation
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A room became present or absent.
+/// The timestamp is in seconds since the unix epoch.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    pub timestamp: u64,
+    pub room: String,
+    pub present: bool,
+}
+
+/// Room presence of the past days.
+/// Every day replays one of the past `days` days at the same time of the day,
+/// shifted by a random jitter per room and day.
+pub struct History {
+    /// timestamp and presence per room, sorted by timestamp
+    rooms: BTreeMap<String, Vec<(u64, bool)>>,
+    days: u64,
+    jitter: u64,
+}
+
+impl History {
+    pub fn new(mut entries: Vec<HistoryEntry>, days: u64, jitter: u64) -> Self {
+        entries.sort_by_key(|entry| entry.timestamp);
+        let mut rooms: BTreeMap<String, Vec<(u64, bool)>> = BTreeMap::new();
+        for entry in entries {
+            rooms
+                .entry(entry.room)
+                .or_default()
+                .push((entry.timestamp, entry.present));
+        }
+        History {
+            rooms,
+            days: days.max(1),
+            jitter,
+        }
+    }
+
+    /// entries which can be replayed at `timestamp`, older entries are dropped
+    pub fn load_from_file(
+        path: &Path,
+        days: u64,
+        jitter: u64,
+        timestamp: u64,
+    ) -> Result<Self, Box<dyn Error>> {
+        let entries = History::read_entries(path, History::keep_since(days, jitter, timestamp))?;
+        info!("loaded {} history entries", entries.len());
+        Ok(History::new(entries, days, jitter))
+    }
+
+    /// drop the entries which can't be replayed anymore from the file
+    pub fn compact_file(
+        path: &Path,
+        days: u64,
+        jitter: u64,
+        timestamp: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let entries = History::read_entries(path, History::keep_since(days, jitter, timestamp))?;
+        // write to a temporary file first, so a crash never loses the history
+        let temporary_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+        for entry in entries.iter() {
+            writeln!(writer, "{}", serde_json::to_string(entry)?)?;
+        }
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+
+    /// the oldest timestamp which can be replayed
+    fn keep_since(days: u64, jitter: u64, timestamp: u64) -> u64 {
+        timestamp.saturating_sub((days.max(1) + 1) * SECONDS_PER_DAY + jitter)
+    }
+
+    /// one json entry per line, broken lines are skipped
+    fn read_entries(path: &Path, keep_since: u64) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            match serde_json::from_str::<HistoryEntry>(&line) {
+                Ok(entry) if entry.timestamp >= keep_since => entries.push(entry),
+                Ok(_) => {}
+                Err(e) => warn!("skip history entry {} : {}", line, e),
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn append_to_file(path: &Path, entries: &[HistoryEntry]) -> Result<(), Box<dyn Error>> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        for entry in entries.iter() {
+            writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        }
+        Ok(())
+    }
+
+    /// rooms which were present in the replayed day at the same time of the day
+    pub fn get_present_rooms(&self, timestamp: u64) -> BTreeSet<String> {
+        let mut present_rooms = BTreeSet::new();
+        for (room, entries) in self.rooms.iter() {
+            let replayed = self.get_replayed_timestamp(room, timestamp);
+            let index = entries.partition_point(|(entry, _)| *entry <= replayed);
+            if index > 0 && entries[index - 1].1 {
+                present_rooms.insert(room.clone());
+            }
+        }
+        present_rooms
+    }
+
+    /// the next timestamp at which the present rooms can change
+    pub fn next_change(&self, timestamp: u64) -> u64 {
+        // the replayed day and the jitter change at midnight
+        let mut next_change = (timestamp / SECONDS_PER_DAY + 1) * SECONDS_PER_DAY;
+        for (room, entries) in self.rooms.iter() {
+            let replayed = self.get_replayed_timestamp(room, timestamp);
+            let index = entries.partition_point(|(entry, _)| *entry <= replayed);
+            if let Some((entry, _)) = entries.get(index) {
+                next_change = next_change.min(timestamp + (entry - replayed));
+            }
+        }
+        next_change
+    }
+
+    /// the timestamp in the replayed day, including the jitter
+    fn get_replayed_timestamp(&self, room: &str, timestamp: u64) -> u64 {
+        let day = timestamp / SECONDS_PER_DAY;
+        let days_back = 1 + day % self.days;
+        let replayed = timestamp.saturating_sub(days_back * SECONDS_PER_DAY);
+        if self.jitter == 0 {
+            return replayed;
+        }
+        // the same for a room during the whole day, so the replay doesn't flicker
+        let mut hasher = DefaultHasher::new();
+        (room, day).hash(&mut hasher);
+        let offset = hasher.finish() % (2 * self.jitter + 1);
+        (replayed + offset).saturating_sub(self.jitter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIDNIGHT: u64 = 1000 * SECONDS_PER_DAY;
+
+    fn entry(timestamp: u64, room: &str, present: bool) -> HistoryEntry {
+        HistoryEntry {
+            timestamp,
+            room: room.to_string(),
+            present,
+        }
+    }
+
+    fn create_history(jitter: u64) -> History {
+        // yesterday, the kitchen was present from 08:00 to 09:00
+        let yesterday = MIDNIGHT - SECONDS_PER_DAY;
+        History::new(
+            vec![
+                entry(yesterday + 9 * 3600, "kitchen", false),
+                entry(yesterday + 8 * 3600, "kitchen", true),
+                entry(yesterday + 20 * 3600, "bed_room", true),
+            ],
+            1,
+            jitter,
+        )
+    }
+
+    #[test]
+    fn test_get_present_rooms() {
+        let history = create_history(0);
+        assert!(history.get_present_rooms(MIDNIGHT + 7 * 3600).is_empty());
+        let present_rooms = history.get_present_rooms(MIDNIGHT + 8 * 3600 + 60);
+        assert_eq!(present_rooms.len(), 1);
+        assert!(present_rooms.contains("kitchen"));
+        assert!(history.get_present_rooms(MIDNIGHT + 10 * 3600).is_empty());
+
+        assert_eq!(history.next_change(MIDNIGHT), MIDNIGHT + 8 * 3600);
+        assert_eq!(
+            history.next_change(MIDNIGHT + 21 * 3600),
+            MIDNIGHT + SECONDS_PER_DAY
+        );
+    }
+
+    #[test]
+    fn test_jitter() {
+        let history = create_history(900);
+        let replayed = history.get_replayed_timestamp("kitchen", MIDNIGHT + 8 * 3600);
+        assert!(replayed >= MIDNIGHT - SECONDS_PER_DAY + 8 * 3600 - 900);
+        assert!(replayed <= MIDNIGHT - SECONDS_PER_DAY + 8 * 3600 + 900);
+        assert_eq!(
+            replayed,
+            history.get_replayed_timestamp("kitchen", MIDNIGHT + 8 * 3600)
+        );
+        // half an hour in, the kitchen is present no matter the jitter
+        let present_rooms = history.get_present_rooms(MIDNIGHT + 8 * 3600 + 1800);
+        assert!(present_rooms.contains("kitchen"));
+    }
+
+    #[test]
+    fn test_append_load_and_compact() {
+        let path = std::env::temp_dir().join("light-control-test-history.jsonl");
+        let _ = std::fs::remove_file(&path);
+        let old = MIDNIGHT - 3 * SECONDS_PER_DAY;
+        History::append_to_file(&path, &[entry(old, "kitchen", true)]).unwrap();
+        History::append_to_file(&path, &[entry(MIDNIGHT - 10, "kitchen", true)]).unwrap();
+        History::append_to_file(&path, &[entry(MIDNIGHT - 5, "kitchen", false)]).unwrap();
+
+        // one replayed day, the entry of three days ago is too old
+        let history = History::load_from_file(&path, 1, 0, MIDNIGHT).unwrap();
+        assert_eq!(
+            history.rooms["kitchen"],
+            vec![(MIDNIGHT - 10, true), (MIDNIGHT - 5, false)]
+        );
+
+        History::compact_file(&path, 1, 0, MIDNIGHT).unwrap();
+        let history = History::load_from_file(&path, 7, 0, MIDNIGHT).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(history.rooms["kitchen"].len(), 2);
+    }
+}
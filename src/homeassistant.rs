@@ -0,0 +1,235 @@
+//! Home Assistant mqtt discovery,
+//! to get light-control entities in Home Assistant without writing yaml.
+
+use light_control::configuration::{Configuration, HomeAssistant};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+/// topic and payload of all discovery configs
+pub fn get_discovery_messages(
+    configuration: &Configuration,
+    homeassistant: &HomeAssistant,
+    control_topic: &str,
+    state_topic: &str,
+) -> Vec<(String, String)> {
+    let node_id = &homeassistant.node_id;
+    let device = json!({
+        "identifiers": [node_id],
+        "name": "light-control",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+    let scenes: Vec<&String> = configuration
+        .scenes
+        .iter()
+        .map(|scene| &scene.name)
+        .collect();
+
+    let mut entities: Vec<(&str, String, Value)> = vec![
+        (
+            "select",
+            "scene".to_string(),
+            json!({
+                "name": "Scene",
+                "options": scenes,
+                "command_topic": control_topic,
+                "command_template": "{\"scene\":\"{{ value }}\"}",
+                "state_topic": state_topic,
+                "value_template": "{{ value_json.scene }}",
+            }),
+        ),
+        (
+            "sensor",
+            "current_room".to_string(),
+            json!({
+                "name": "Current room",
+                "state_topic": state_topic,
+                "value_template": "{{ value_json.current_room }}",
+            }),
+        ),
+        (
+            "number",
+            "brightness".to_string(),
+            json!({
+                "name": "Brightness",
+                "min": 0,
+                "max": 255,
+                "command_topic": control_topic,
+                "command_template": "{\"brightness\":{{ value | int }}}",
+                "state_topic": state_topic,
+                "value_template": "{{ value_json.brightness }}",
+            }),
+        ),
+        (
+            "switch",
+            "room_tracking".to_string(),
+            json!({
+                "name": "Room tracking",
+                "command_topic": control_topic,
+                "payload_on": "{\"room_tracking\":true}",
+                "payload_off": "{\"room_tracking\":false}",
+                "state_topic": state_topic,
+                "value_template": "{{ 'ON' if value_json.room_tracking else 'OFF' }}",
+                "state_on": "ON",
+                "state_off": "OFF",
+            }),
+        ),
+    ];
+    let mut object_ids = BTreeSet::new();
+    for room in configuration.get_rooms() {
+        // a json string is a valid jinja string, quotes and backslashes are escaped
+        let room_literal = serde_json::to_string(&room).unwrap();
+        // rooms are sorted, so the suffix of a colliding slug is stable
+        let slug = slugify(&room);
+        let mut object_id = format!("{}_presence", slug);
+        let mut suffix = 2;
+        while !object_ids.insert(object_id.clone()) {
+            object_id = format!("{}_{}_presence", slug, suffix);
+            suffix += 1;
+        }
+        if suffix > 2 {
+            warn!(
+                "room {} is published as {}, its name collides with another room",
+                room, object_id
+            );
+        }
+        entities.push((
+            "binary_sensor",
+            object_id,
+            json!({
+                "name": format!("{} presence", room),
+                "device_class": "occupancy",
+                "state_topic": state_topic,
+                "value_template": format!(
+                    "{{{{ 'ON' if value_json.rooms[{}] else 'OFF' }}}}",
+                    room_literal
+                ),
+            }),
+        ));
+    }
+
+    entities
+        .into_iter()
+        .map(|(component, object_id, mut config)| {
+            config["unique_id"] = json!(format!("{}_{}", node_id, object_id));
+            config["device"] = device.clone();
+            let topic = format!(
+                "{}/{}/{}/{}/config",
+                homeassistant.discovery_prefix, component, node_id, object_id
+            );
+            (topic, config.to_string())
+        })
+        .collect()
+}
+
+/// object ids must only contain `[a-zA-Z0-9_-]`
+fn slugify(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_discovery_messages() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/home.json");
+        let configuration = Configuration::load_from_file(path).unwrap();
+        let homeassistant = HomeAssistant {
+            discovery_prefix: HomeAssistant::default_discovery_prefix(),
+            node_id: HomeAssistant::default_node_id(),
+        };
+        let messages = get_discovery_messages(
+            &configuration,
+            &homeassistant,
+            "control/lights/set",
+            "control/lights/state",
+        );
+        let (topic, payload) = messages.first().unwrap();
+        assert_eq!(topic, "homeassistant/select/light_control/scene/config");
+        let payload: Value = serde_json::from_str(payload).unwrap();
+        assert_eq!(payload["options"], json!(["default", "evening", "night"]));
+        assert_eq!(payload["unique_id"], json!("light_control_scene"));
+
+        let (topic, payload) = messages.last().unwrap();
+        assert_eq!(
+            topic,
+            "homeassistant/binary_sensor/light_control/bed_room_presence/config"
+        );
+        let payload: Value = serde_json::from_str(payload).unwrap();
+        assert_eq!(
+            payload["value_template"],
+            json!("{{ 'ON' if value_json.rooms[\"bed_room\"] else 'OFF' }}")
+        );
+    }
+
+    #[test]
+    fn test_room_names_are_escaped() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/home.json");
+        let mut configuration = Configuration::load_from_file(path).unwrap();
+        for sensor in configuration.sensors.iter_mut() {
+            sensor.room = "kid's room".to_string();
+        }
+        let homeassistant = HomeAssistant {
+            discovery_prefix: HomeAssistant::default_discovery_prefix(),
+            node_id: HomeAssistant::default_node_id(),
+        };
+        let messages = get_discovery_messages(
+            &configuration,
+            &homeassistant,
+            "control/lights/set",
+            "control/lights/state",
+        );
+        let (topic, payload) = messages.last().unwrap();
+        assert_eq!(
+            topic,
+            "homeassistant/binary_sensor/light_control/kid_s_room_presence/config"
+        );
+        let payload: Value = serde_json::from_str(payload).unwrap();
+        assert_eq!(
+            payload["value_template"],
+            json!("{{ 'ON' if value_json.rooms[\"kid's room\"] else 'OFF' }}")
+        );
+    }
+
+    #[test]
+    fn test_colliding_room_names_get_a_suffix() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/home.json");
+        let mut configuration = Configuration::load_from_file(path).unwrap();
+        let mut first = configuration.sensors[0].clone();
+        let mut second = first.clone();
+        first.room = "kid's room".to_string();
+        second.room = "kid s room".to_string();
+        configuration.sensors = vec![first, second];
+        let homeassistant = HomeAssistant {
+            discovery_prefix: HomeAssistant::default_discovery_prefix(),
+            node_id: HomeAssistant::default_node_id(),
+        };
+        let messages = get_discovery_messages(
+            &configuration,
+            &homeassistant,
+            "control/lights/set",
+            "control/lights/state",
+        );
+        let topics: Vec<&String> = messages
+            .iter()
+            .rev()
+            .take(2)
+            .map(|(topic, _)| topic)
+            .collect();
+        assert_eq!(
+            topics,
+            vec![
+                "homeassistant/binary_sensor/light_control/kid_s_room_2_presence/config",
+                "homeassistant/binary_sensor/light_control/kid_s_room_presence/config",
+            ]
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Embedded http server, serving metrics and a json api
+
+use crate::metrics::Metrics;
+use light_control::configuration::{Configuration, SwitchState};
+use light_control::devices::DeviceMonitor;
+use light_control::engine::EngineStatus;
+use light_control::UpdateMessage;
+use serde::Deserialize;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Method, Response, Server};
+
+/// everything the http server needs to answer requests
+pub struct HttpContext {
+    pub configuration: Configuration,
+    pub metrics: Arc<Mutex<Metrics>>,
+    /// last known engine status
+    pub status: Arc<Mutex<Option<EngineStatus>>>,
+    /// battery and link quality of the devices, if monitored
+    pub devices: Option<Arc<Mutex<DeviceMonitor>>>,
+    /// send updates to the decision engine
+    pub update_sender: Sender<UpdateMessage>,
+}
+
+#[derive(Deserialize)]
+struct SceneRequest {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct BrightnessRequest {
+    brightness: u8,
+}
+
+#[derive(Deserialize)]
+struct SwitchRequest {
+    /// switch topic, switch name, group or tag
+    topic: String,
+    /// None removes the override
+    state: Option<SwitchState>,
+}
+
+/// start the http server in its own thread
+pub fn start(address: &str, context: HttpContext) {
+    let server = match Server::http(address) {
+        Ok(server) => server,
+        Err(e) => {
+            error!("couldn't start http server on {} : {}", address, e);
+            return;
+        }
+    };
+    info!("http server listening on {}", address);
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            if let Err(e) = request.as_reader().read_to_string(&mut body) {
+                warn!("couldn't read http request : {}", e);
+                continue;
+            }
+            let (status_code, content_type, body) =
+                handle_request(&context, request.method(), request.url(), &body);
+            let content_type = Header::from_bytes("Content-Type", content_type).unwrap();
+            let response = Response::from_string(body)
+                .with_status_code(status_code)
+                .with_header(content_type);
+            if let Err(e) = request.respond(response) {
+                warn!("couldn't send http response : {}", e);
+            }
+        }
+    });
+}
+
+/// status code, content type and body for a request
+fn handle_request(
+    context: &HttpContext,
+    method: &Method,
+    url: &str,
+    body: &str,
+) -> (u16, &'static str, String) {
+    let update_messages = match (method, url) {
+        (Method::Get, "/metrics") => {
+            let metrics = context.metrics.lock().unwrap().render();
+            return (200, "text/plain; version=0.0.4", metrics);
+        }
+        (Method::Get, "/state") => {
+            let status = context.status.lock().unwrap();
+            return json_response(200, &*status);
+        }
+        (Method::Get, "/devices") => match &context.devices {
+            None => return error_response(404, "device monitoring is not configured"),
+            Some(devices) => {
+                let devices = devices.lock().unwrap();
+                return json_response(200, devices.get_devices());
+            }
+        },
+        (Method::Post, "/scene") => match serde_json::from_str::<SceneRequest>(body) {
+            Err(e) => return error_response(400, &e.to_string()),
+            Ok(request) => match context.configuration.get_scene(&request.name) {
+                None => return error_response(404, "unknown scene"),
+                Some(scene) => {
+                    info!("change scene to {}", request.name);
+                    vec![UpdateMessage::SceneChange(scene.clone())]
+                }
+            },
+        },
+        (Method::Post, "/brightness") => match serde_json::from_str::<BrightnessRequest>(body) {
+            Err(e) => return error_response(400, &e.to_string()),
+            Ok(request) => {
+                info!("change brightness to {}", request.brightness);
+                vec![UpdateMessage::BrightnessChange(request.brightness)]
+            }
+        },
+        (Method::Post, "/switch") => match serde_json::from_str::<SwitchRequest>(body) {
+            Err(e) => return error_response(400, &e.to_string()),
+            Ok(request) => {
+                if request.state == Some(SwitchState::Unknown) {
+                    return error_response(400, "state must be On, Off or null");
+                }
+                let topics = context
+                    .configuration
+                    .resolve_switch_reference(&request.topic);
+                if topics.is_empty() {
+                    return error_response(404, "unknown switch");
+                }
+                info!("override {:?} with {:?}", topics, request.state);
+                topics
+                    .into_iter()
+                    .map(|topic| UpdateMessage::SwitchOverride(topic, request.state))
+                    .collect()
+            }
+        },
+        _ => return error_response(404, "not found"),
+    };
+    for update_message in update_messages {
+        if context.update_sender.send(update_message).is_err() {
+            return error_response(503, "decision engine is not running");
+        }
+    }
+    json_response(202, &serde_json::json!({ "accepted": true }))
+}
+
+fn json_response<T: serde::Serialize>(status_code: u16, value: &T) -> (u16, &'static str, String) {
+    (
+        status_code,
+        "application/json",
+        serde_json::to_string(value).unwrap(),
+    )
+}
+
+fn error_response(status_code: u16, error: &str) -> (u16, &'static str, String) {
+    json_response(status_code, &serde_json::json!({ "error": error }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::sync::mpsc::Receiver;
+
+    fn create_context() -> (HttpContext, Receiver<UpdateMessage>) {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/home.json");
+        let configuration = Configuration::load_from_file(path).unwrap();
+        let (update_sender, update_receiver) = mpsc::channel();
+        let context = HttpContext {
+            metrics: Arc::new(Mutex::new(Metrics::new(&configuration))),
+            configuration,
+            status: Arc::new(Mutex::new(None)),
+            devices: None,
+            update_sender,
+        };
+        (context, update_receiver)
+    }
+
+    #[test]
+    fn test_post_scene() {
+        let (context, update_receiver) = create_context();
+        let (status_code, _, _) =
+            handle_request(&context, &Method::Post, "/scene", r#"{"name":"night"}"#);
+        assert_eq!(status_code, 202);
+        match update_receiver.try_recv() {
+            Ok(UpdateMessage::SceneChange(scene)) => assert_eq!(scene.name, "night"),
+            _ => panic!("scene change expected"),
+        }
+
+        let (status_code, _, _) =
+            handle_request(&context, &Method::Post, "/scene", r#"{"name":"party"}"#);
+        assert_eq!(status_code, 404);
+        assert!(update_receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_post_switch() {
+        let (context, update_receiver) = create_context();
+        let body = r#"{"topic":"zigbee2mqtt/light_8","state":"Off"}"#;
+        let (status_code, _, _) = handle_request(&context, &Method::Post, "/switch", body);
+        assert_eq!(status_code, 202);
+        match update_receiver.try_recv() {
+            Ok(UpdateMessage::SwitchOverride(topic, state)) => {
+                assert_eq!(topic, "zigbee2mqtt/light_8");
+                assert_eq!(state, Some(SwitchState::Off));
+            }
+            _ => panic!("switch override expected"),
+        }
+
+        let body = r#"{"topic":"zigbee2mqtt/light_8","state":"Unknown"}"#;
+        let (status_code, _, _) = handle_request(&context, &Method::Post, "/switch", body);
+        assert_eq!(status_code, 400);
+        assert!(update_receiver.try_recv().is_err());
+
+        let (status_code, _, _) = handle_request(
+            &context,
+            &Method::Post,
+            "/brightness",
+            r#"{"brightness":300}"#,
+        );
+        assert_eq!(status_code, 400);
+    }
+
+    #[test]
+    fn test_post_switch_by_name() {
+        let (mut context, update_receiver) = create_context();
+        context.configuration.switches[0].name = Some("bed_light".to_string());
+        let body = r#"{"topic":"bed_light","state":null}"#;
+        let (status_code, _, _) = handle_request(&context, &Method::Post, "/switch", body);
+        assert_eq!(status_code, 202);
+        match update_receiver.try_recv() {
+            Ok(UpdateMessage::SwitchOverride(topic, state)) => {
+                assert_eq!(topic, "zigbee2mqtt/light_8");
+                assert_eq!(state, None);
+            }
+            _ => panic!("switch override expected"),
+        }
+
+        let body = r#"{"topic":"garage_light","state":"On"}"#;
+        let (status_code, _, _) = handle_request(&context, &Method::Post, "/switch", body);
+        assert_eq!(status_code, 404);
+    }
+
+    #[test]
+    fn test_get_state() {
+        let (context, _) = create_context();
+        let (status_code, content_type, body) =
+            handle_request(&context, &Method::Get, "/state", "");
+        assert_eq!(status_code, 200);
+        assert_eq!(content_type, "application/json");
+        assert_eq!(body, "null");
+
+        let (status_code, _, _) = handle_request(&context, &Method::Get, "/devices", "");
+        assert_eq!(status_code, 404);
+    }
+}
@@ -0,0 +1,65 @@
+//! Room tracking light control.
+//!
+//! Load a [`Configuration`](configuration::Configuration),
+//! create a decision engine with [`create_engine`](engine::create_engine)
+//! and feed it [`UpdateMessage`]s. The engine returns the
+//! [`SwitchCommand`](strategy::SwitchCommand)s which should be send to the switches.
+
+#[macro_use]
+extern crate log;
+extern crate serde_json;
+
+pub mod configuration;
+pub mod devices;
+mod dummy_configuration;
+pub mod engine;
+pub mod history;
+pub mod presence;
+pub mod snapshot;
+pub mod strategy;
+
+use crate::configuration::{Overlay, Scene, SensorState, SwitchState};
+use crate::history::History;
+use std::time::Instant;
+
+/// Object used to send messages to the main decision engine
+pub enum UpdateMessage {
+    /// Send a Scene change
+    SceneChange(Scene),
+    /// activate the scene after the active scene in the list
+    SceneCycle(Vec<Scene>),
+    /// stack an overlay on top of the active scene
+    AddOverlay(Overlay),
+    /// remove an overlay by name
+    RemoveOverlay(String),
+    /// Send a State change
+    SwitchChange(Instant, SwitchChangeContent),
+    /// Send a State change
+    SensorChange(Instant, SensorChangeContent),
+    /// a sensor sent a message, even without a state
+    SensorHeartbeat(Instant, String),
+    /// change the number of tracked occupants
+    OccupantsChange(usize),
+    /// change the brightness of the active scene
+    BrightnessChange(u8),
+    /// enable or disable room tracking
+    RoomTrackingChange(bool),
+    /// enable room tracking if it is disabled and vice versa
+    RoomTrackingToggle,
+    /// start the presence simulation with the recorded history, None stops it
+    VacationChange(Option<History>),
+    /// override the state of a switch, None removes the override
+    SwitchOverride(String, Option<SwitchState>),
+    /// a deadline is reached, recalculate the state
+    Ping,
+}
+
+pub struct SwitchChangeContent {
+    pub topic: String,
+    pub state: SwitchState,
+}
+
+pub struct SensorChangeContent {
+    pub topic: String,
+    pub state: SensorState,
+}
@@ -4,33 +4,62 @@ extern crate env_logger;
 extern crate paho_mqtt;
 extern crate serde_json;
 
-mod configuration;
-mod dummy_configuration;
+mod homeassistant;
+mod http;
+mod metrics;
 mod mqtt;
+mod publish_queue;
 mod replay;
-mod strategy;
 
-use crate::configuration::{Configuration, SensorState, SwitchState};
+use crate::homeassistant::get_discovery_messages;
+use crate::http::HttpContext;
+use crate::metrics::Metrics;
 use crate::mqtt::MqttClient;
+use crate::publish_queue::PublishQueue;
 use crate::replay::Replay;
-use crate::strategy::{Strategy, SwitchCommand};
+use light_control::configuration::{Configuration, Trigger, TriggerAction};
+use light_control::devices::DeviceMonitor;
+use light_control::engine::{create_engine, EngineStatus};
+use light_control::history::{History, HistoryEntry};
+use light_control::snapshot::now_timestamp;
+use light_control::snapshot::Snapshot;
+use light_control::strategy::SwitchCommand;
+use light_control::{SensorChangeContent, SwitchChangeContent, UpdateMessage};
 use paho_mqtt::MessageBuilder;
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
 const LIGHT_CONTROL_SET_TOPIC: &str = "control/lights/set";
-const PING_PERIOD: u64 = 3;
+const LIGHT_CONTROL_STATE_TOPIC: &str = "control/lights/state";
+const LIGHT_CONTROL_HEALTH_TOPIC: &str = "control/lights/health";
+/// wait a bit longer than the deadline, so delays are definitely reached
+const DEADLINE_SLACK: Duration = Duration::from_millis(10);
+/// drop history entries which can't be replayed anymore once a day
+const HISTORY_COMPACT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
 
 /// commands which can be send to control/lights/set
 #[derive(Deserialize)]
 pub struct LightControlSetCommand {
     /// change the scene to the given scene name
     pub scene: Option<String>,
+    /// change the number of occupants
+    pub occupants: Option<usize>,
+    /// change the brightness of the active scene
+    pub brightness: Option<u8>,
+    /// enable or disable room tracking
+    pub room_tracking: Option<bool>,
+    /// stack the overlay with the given name on top of the scene
+    pub add_overlay: Option<String>,
+    /// remove the overlay with the given name
+    pub remove_overlay: Option<String>,
+    /// start or stop the presence simulation
+    pub vacation: Option<bool>,
 }
 
 #[derive(StructOpt)]
@@ -45,6 +74,12 @@ struct Opt {
     /// replay configuration output path
     #[structopt(long, parse(from_os_str))]
     replay_config: Option<PathBuf>,
+    /// state file, to restore the runtime state after a restart
+    #[structopt(long, parse(from_os_str))]
+    state_file: Option<PathBuf>,
+    /// max age (in seconds) of the state file, older state files are not restored
+    #[structopt(long, default_value = "600")]
+    state_max_age: u64,
 }
 
 fn main() {
@@ -69,6 +104,20 @@ fn main() {
             .verify()
             .expect("couldn't verify scene, see log for more information");
     }
+    configuration
+        .verify_qos()
+        .expect("couldn't verify qos, see log for more information");
+    configuration
+        .verify_rate_limit()
+        .expect("couldn't verify rate limit, see log for more information");
+    configuration
+        .verify_triggers()
+        .expect("couldn't verify triggers, see log for more information");
+    if let Some(circadian) = configuration.circadian.as_ref() {
+        circadian
+            .verify()
+            .expect("couldn't verify circadian, see log for more information");
+    }
 
     let mut replay = None;
     match (opt.replay_config, opt.replay_script) {
@@ -91,16 +140,17 @@ fn main() {
         });
     }
 
-    let mut topics_to_subscribe = configuration.get_topics();
-    let mut strategy = Strategy::new(&configuration);
-    let light_control_topic = LIGHT_CONTROL_SET_TOPIC.to_string();
-    topics_to_subscribe.push(&light_control_topic);
+    let mut subscriptions = configuration.get_subscriptions();
+    subscriptions.push((
+        LIGHT_CONTROL_SET_TOPIC.to_string(),
+        configuration.control.qos,
+    ));
     // connect and subscribe to mqtt
-    let mut mqtt_client = MqttClient::new(
+    let (mut mqtt_client, mqtt_receiver) = MqttClient::new(
         configuration.credentials.host.clone(),
         configuration.credentials.user.clone(),
         configuration.credentials.password.clone(),
-        topics_to_subscribe,
+        subscriptions,
     );
 
     // trigger status updates for devices
@@ -112,7 +162,7 @@ fn main() {
         let message = MessageBuilder::new()
             .topic(&switch.command.topic)
             .payload(init_command.as_str())
-            .qos(0)
+            .qos(switch.command.qos)
             .finalize();
         mqtt_client.cli.publish(message);
     }
@@ -124,12 +174,19 @@ fn main() {
 
     // start thread which reacts on state changes
     let state_configuration = configuration.clone();
-    let mqtt_receiver = mqtt_client.cli.start_consuming();
+    let metrics = Arc::new(Mutex::new(Metrics::new(&configuration)));
+    let consumer_metrics = metrics.clone();
+    let devices = configuration
+        .device_monitoring
+        .clone()
+        .map(|monitoring| Arc::new(Mutex::new(DeviceMonitor::new(&configuration, monitoring))));
+    let consumer_devices = devices.clone();
     thread::spawn(move || {
         for msg in mqtt_receiver.iter() {
             if let Some(msg) = msg {
                 let topic = msg.topic();
                 let payload_str = msg.payload_str();
+                consumer_metrics.lock().unwrap().message_received(topic);
 
                 if is_replay_enabled {
                     replay_sender.send(ReplayMessage {
@@ -142,7 +199,10 @@ fn main() {
                     let command =
                         serde_json::from_str(&payload_str).map(|a: LightControlSetCommand| a);
                     match command {
-                        Err(e) => error!("couldn't parse {} : {}", LIGHT_CONTROL_SET_TOPIC, e),
+                        Err(e) => {
+                            error!("couldn't parse {} : {}", LIGHT_CONTROL_SET_TOPIC, e);
+                            consumer_metrics.lock().unwrap().parse_failure(topic);
+                        }
                         Ok(command) => {
                             command
                                 .scene
@@ -154,20 +214,91 @@ fn main() {
                                 .flatten()
                                 .map(|(name, scene)| {
                                     info!("change scene to {}", name);
-                                    change_sender.send(UpdateMessage::SceneChange {
-                                        disabled_switches: scene.disabled_switches.clone(),
-                                        enabled_switches: scene.enabled_switches.clone(),
-                                        ignored_switches: scene.ignored_switches.clone(),
-                                        brightness: scene.brightness,
-                                        enable_room_tracking: scene.room_tracking_enabled,
-                                        ignored_sensors: scene.ignored_sensors.clone(),
-                                    })
+                                    change_sender.send(UpdateMessage::SceneChange(scene.clone()))
                                 });
+                            command.occupants.map(|occupants| {
+                                info!("change occupants to {}", occupants);
+                                change_sender.send(UpdateMessage::OccupantsChange(occupants))
+                            });
+                            if let Some(brightness) = command.brightness {
+                                info!("change brightness to {}", brightness);
+                                change_sender
+                                    .send(UpdateMessage::BrightnessChange(brightness))
+                                    .expect("decision engine is not running");
+                            }
+                            if let Some(room_tracking) = command.room_tracking {
+                                info!("change room tracking to {}", room_tracking);
+                                change_sender
+                                    .send(UpdateMessage::RoomTrackingChange(room_tracking))
+                                    .expect("decision engine is not running");
+                            }
+                            if let Some(name) = command.add_overlay {
+                                match state_configuration.get_overlay(&name) {
+                                    Some(overlay) => {
+                                        info!("add overlay {}", name);
+                                        change_sender
+                                            .send(UpdateMessage::AddOverlay(overlay.clone()))
+                                            .expect("decision engine is not running");
+                                    }
+                                    None => warn!("unknown overlay {}", name),
+                                }
+                            }
+                            if let Some(name) = command.remove_overlay {
+                                info!("remove overlay {}", name);
+                                change_sender
+                                    .send(UpdateMessage::RemoveOverlay(name))
+                                    .expect("decision engine is not running");
+                            }
+                            match (command.vacation, &state_configuration.vacation) {
+                                (Some(true), Some(vacation)) => match History::load_from_file(
+                                    Path::new(&vacation.history_file),
+                                    vacation.days,
+                                    vacation.jitter,
+                                    now_timestamp(),
+                                ) {
+                                    Ok(history) => {
+                                        change_sender
+                                            .send(UpdateMessage::VacationChange(Some(history)))
+                                            .expect("decision engine is not running");
+                                    }
+                                    Err(e) => warn!(
+                                        "couldn't load history {} : {}",
+                                        vacation.history_file, e
+                                    ),
+                                },
+                                (Some(false), _) => {
+                                    change_sender
+                                        .send(UpdateMessage::VacationChange(None))
+                                        .expect("decision engine is not running");
+                                }
+                                (Some(true), None) => warn!("vacation is not configured"),
+                                (None, _) => {}
+                            }
                         }
                     }
                 } else {
+                    // a sensor change records the heartbeat as well
+                    let mut sensor_changed = false;
                     match serde_json::from_str(&payload_str) {
                         Result::Ok(payload) => {
+                            if let Some(devices) = &consumer_devices {
+                                devices.lock().unwrap().update(topic, &payload);
+                            }
+                            // retained messages would replay the actions on every (re)connect
+                            let triggers = if msg.retained() {
+                                vec![]
+                            } else {
+                                state_configuration.get_triggers_for_message(topic, &payload)
+                            };
+                            for trigger in triggers {
+                                if let Some(update_message) =
+                                    get_trigger_update_message(&state_configuration, trigger)
+                                {
+                                    change_sender
+                                        .send(update_message)
+                                        .expect("decision engine is not running");
+                                }
+                            }
                             state_configuration
                                 .get_update_switch_for_topic(topic, &payload)
                                 .map(|(topic, state)| {
@@ -175,138 +306,335 @@ fn main() {
                                     change_sender
                                         .send(UpdateMessage::SwitchChange(Instant::now(), content));
                                 });
-                            state_configuration
-                                .get_update_sensor_for_topic(topic, &payload)
-                                .map(|(topic, state)| {
-                                    let content = SensorChangeContent { topic, state };
-                                    change_sender
-                                        .send(UpdateMessage::SensorChange(Instant::now(), content));
-                                });
+                            if let Some((topic, state)) =
+                                state_configuration.get_update_sensor_for_topic(topic, &payload)
+                            {
+                                let content = SensorChangeContent { topic, state };
+                                change_sender
+                                    .send(UpdateMessage::SensorChange(Instant::now(), content));
+                                sensor_changed = true;
+                            }
                         }
-                        _ => {}
+                        _ => {
+                            debug!("couldn't parse message on {}", topic);
+                            consumer_metrics.lock().unwrap().parse_failure(topic);
+                        }
+                    }
+                    let is_sensor = state_configuration
+                        .get_sensor_for_topic(topic.to_string())
+                        .is_some();
+                    if is_sensor && !sensor_changed {
+                        change_sender
+                            .send(UpdateMessage::SensorHeartbeat(
+                                Instant::now(),
+                                topic.to_string(),
+                            ))
+                            .expect("decision engine is not running");
                     }
                 }
             }
         }
     });
 
-    // start thread that triggers regular ping messages
-    let ping_sender = update_sender.clone();
-    thread::spawn(move || loop {
-        thread::sleep(Duration::from_secs(PING_PERIOD));
-        ping_sender.send(UpdateMessage::Ping);
-    });
-
-    // take over all devices after a while
-    let deinit_sender = update_sender.clone();
-    let takeover_delay = configuration.get_max_sensor_delay() + 10;
-    info!("takeover delay : {}s", takeover_delay);
-    thread::spawn(move || {
-        let instant = Instant::now();
-        thread::sleep(Duration::from_secs(takeover_delay));
-        deinit_sender.send(UpdateMessage::Deinit(instant));
-    });
-
     // publish thread
     let publish_configuration = configuration.clone();
-    let (publish_sender, publish_receiver): (Sender<SwitchCommand>, Receiver<SwitchCommand>) =
+    let (publish_sender, publish_receiver): (Sender<PublishMessage>, Receiver<PublishMessage>) =
         mpsc::channel();
     thread::spawn(move || {
-        for message in publish_receiver.iter() {
-            let switch = publish_configuration
-                .get_switch_for_topic(message.topic)
-                .expect("couldn't get swtich from topic");
-            let (topic, command) = switch.get_topic_and_command(message.state, message.brightness);
-            let mqtt_message = MessageBuilder::new()
-                .topic(topic)
-                .payload(command)
-                .qos(0)
-                .finalize();
-            mqtt_client.cli.publish(mqtt_message);
+        let mut queue =
+            PublishQueue::new(publish_configuration.rate_limit.as_ref(), Instant::now());
+        loop {
+            // sleep until the next message or until queued commands can be sent
+            let message = match queue.next_ready() {
+                Some(ready) => {
+                    match publish_receiver
+                        .recv_timeout(ready.saturating_duration_since(Instant::now()))
+                    {
+                        Ok(message) => Some(message),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                None => match publish_receiver.recv() {
+                    Ok(message) => Some(message),
+                    Err(_) => break,
+                },
+            };
+            let message = match message {
+                None => None,
+                Some(PublishMessage::Switch(message)) => Some(message),
+                Some(PublishMessage::Raw {
+                    topic,
+                    payload,
+                    qos,
+                    retained,
+                }) => {
+                    let mqtt_message = MessageBuilder::new()
+                        .topic(topic)
+                        .payload(payload)
+                        .qos(qos)
+                        .retained(retained)
+                        .finalize();
+                    if let Err(e) = mqtt_client.cli.publish(mqtt_message) {
+                        warn!("couldn't publish : {}", e);
+                    }
+                    continue;
+                }
+            };
+            if let Some(message) = message {
+                let switch = publish_configuration
+                    .get_switch_for_topic(message.topic.clone())
+                    .expect("couldn't get swtich from topic");
+                let (topic, command) = switch.get_topic_and_command(
+                    message.state,
+                    message.brightness,
+                    message.color_temp,
+                    message.color.as_ref(),
+                );
+                let mqtt_message = MessageBuilder::new()
+                    .topic(topic)
+                    .payload(command)
+                    .qos(switch.command.qos)
+                    .retained(switch.command.retain)
+                    .finalize();
+                queue.push(message.topic, topic.clone(), mqtt_message);
+            }
+            while let Some(mqtt_message) = queue.pop(Instant::now()) {
+                if let Err(e) = mqtt_client.cli.publish(mqtt_message) {
+                    warn!("couldn't publish : {}", e);
+                }
+            }
         }
     });
 
+    // publish Home Assistant discovery configs
+    if let Some(homeassistant) = &configuration.homeassistant {
+        for (topic, payload) in get_discovery_messages(
+            &configuration,
+            homeassistant,
+            LIGHT_CONTROL_SET_TOPIC,
+            LIGHT_CONTROL_STATE_TOPIC,
+        ) {
+            publish_sender
+                .send(PublishMessage::Raw {
+                    topic,
+                    payload,
+                    qos: configuration.control.qos,
+                    retained: true,
+                })
+                .expect("publish thread is not running");
+        }
+    }
+
+    // start http server
+    let shared_status = Arc::new(Mutex::new(None));
+    if let Some(http) = &configuration.http {
+        let context = HttpContext {
+            configuration: configuration.clone(),
+            metrics: metrics.clone(),
+            status: shared_status.clone(),
+            devices: devices.clone(),
+            update_sender: update_sender.clone(),
+        };
+        http::start(&http.address, context);
+    }
+
     // main loop
-    for update_message in update_receiver.iter() {
-        match update_message {
-            UpdateMessage::Ping => {
-                strategy.calculate_current_room();
+    let mut engine = create_engine(&configuration);
+    let mut last_status: Option<EngineStatus> = None;
+    let mut last_health = None;
+    let mut history_compacted: Option<Instant> = None;
+    let mut last_snapshot: Option<Snapshot> = None;
+    if let Some(state_file) = &opt.state_file {
+        match Snapshot::load_from_file(state_file) {
+            Ok(snapshot) if snapshot.age() <= Duration::from_secs(opt.state_max_age) => {
+                info!("restore state from {}", state_file.display());
+                engine.restore(&snapshot, &configuration);
             }
-            UpdateMessage::Deinit(instant) => {
-                strategy.replace_uninitialized_with_absents(instant);
+            Ok(snapshot) => info!(
+                "state file is too old ({}s), start without it",
+                snapshot.age().as_secs()
+            ),
+            Err(e) => warn!("couldn't load state file {} : {}", state_file.display(), e),
+        }
+    }
+    loop {
+        // sleep until the next update or the next deadline of the engine
+        let update_message = match engine.next_deadline() {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now()) + DEADLINE_SLACK;
+                match update_receiver.recv_timeout(timeout) {
+                    Ok(update_message) => update_message,
+                    Err(RecvTimeoutError::Timeout) => UpdateMessage::Ping,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
             }
-            UpdateMessage::SwitchChange(instant, switch_content) => {
-                strategy.update_switch(instant, switch_content);
+            None => match update_receiver.recv() {
+                Ok(update_message) => update_message,
+                Err(_) => break,
+            },
+        };
+        let handle_started = Instant::now();
+        let switch_commands = engine.handle(update_message);
+        let mut metrics = metrics.lock().unwrap();
+        metrics.handle(handle_started.elapsed());
+        for switch_command in switch_commands {
+            metrics.switch_command(&switch_command.topic, switch_command.state);
+            publish_sender
+                .send(PublishMessage::Switch(switch_command))
+                .expect("publish thread is not running");
+        }
+        let status = engine.status();
+        metrics.set_status(status.clone());
+        drop(metrics);
+        *shared_status.lock().unwrap() = Some(status.clone());
+        let mut health = serde_json::json!({
+            "stale_sensors": status.stale_sensors,
+            "unresponsive_switches": status.unresponsive_switches,
+        });
+        if let Some(devices) = &devices {
+            let devices = devices.lock().unwrap();
+            health["low_battery"] = serde_json::json!(devices.get_low_battery());
+            health["weak_link"] = serde_json::json!(devices.get_weak_link());
+        }
+        if last_health.as_ref() != Some(&health) {
+            publish_sender
+                .send(PublishMessage::Raw {
+                    topic: LIGHT_CONTROL_HEALTH_TOPIC.to_string(),
+                    payload: health.to_string(),
+                    qos: configuration.control.qos,
+                    retained: configuration.control.retain,
+                })
+                .expect("publish thread is not running");
+            last_health = Some(health);
+        }
+        if let Some(away) = &configuration.away {
+            let last_away = last_status.as_ref().map(|status| status.away);
+            if last_away != Some(status.away) {
+                publish_sender
+                    .send(PublishMessage::Raw {
+                        topic: away.topic.clone(),
+                        payload: serde_json::json!({ "away": status.away }).to_string(),
+                        qos: configuration.control.qos,
+                        retained: configuration.control.retain,
+                    })
+                    .expect("publish thread is not running");
             }
-            UpdateMessage::SensorChange(instant, sensor_content) => {
-                strategy.update_sensor(instant, sensor_content);
+        }
+        if let Some(vacation) = &configuration.vacation {
+            let path = Path::new(&vacation.history_file);
+            let entries = get_history_entries(last_status.as_ref(), &status, now_timestamp());
+            if !entries.is_empty() {
+                if let Err(e) = History::append_to_file(path, &entries) {
+                    warn!("couldn't write history {} : {}", vacation.history_file, e);
+                }
             }
-            UpdateMessage::SceneChange {
-                disabled_switches,
-                enabled_switches,
-                ignored_switches,
-                brightness,
-                enable_room_tracking,
-                ignored_sensors,
-            } => {
-                strategy.set_brightness(brightness);
-                strategy.set_room_tracking_enabled(enable_room_tracking);
-                strategy.set_disabled_switches(disabled_switches);
-                strategy.set_enabled_switches(enabled_switches);
-                strategy.set_ignored_switches(ignored_switches);
-                strategy.set_ignored_sensors(ignored_sensors);
-
-                for switch_command in strategy.trigger_commands(true) {
-                    publish_sender.send(switch_command);
+            let compact = match history_compacted {
+                Some(compacted) => compacted.elapsed() >= HISTORY_COMPACT_INTERVAL,
+                None => true,
+            };
+            if compact && path.exists() {
+                let result =
+                    History::compact_file(path, vacation.days, vacation.jitter, now_timestamp());
+                if let Err(e) = result {
+                    warn!("couldn't compact history {} : {}", vacation.history_file, e);
+                }
+                history_compacted = Some(Instant::now());
+            }
+        }
+        if last_status.as_ref() != Some(&status) {
+            publish_sender
+                .send(PublishMessage::Raw {
+                    topic: LIGHT_CONTROL_STATE_TOPIC.to_string(),
+                    payload: serde_json::to_string(&status).unwrap(),
+                    qos: configuration.control.qos,
+                    retained: configuration.control.retain,
+                })
+                .expect("publish thread is not running");
+            last_status = Some(status);
+        }
+        if let Some(state_file) = &opt.state_file {
+            let snapshot = engine.snapshot();
+            // refreshed before it gets too old to be restored
+            let changed = match &last_snapshot {
+                Some(last_snapshot) => {
+                    !snapshot.is_same_state(last_snapshot)
+                        || last_snapshot.age() >= Duration::from_secs(opt.state_max_age / 2)
+                }
+                None => true,
+            };
+            if changed {
+                match snapshot.save_to_file(state_file) {
+                    Ok(()) => last_snapshot = Some(snapshot),
+                    Err(e) => warn!("couldn't write state file {} : {}", state_file.display(), e),
                 }
-                continue;
             }
-        };
-        for switch_command in strategy.trigger_commands(false) {
-            publish_sender.send(switch_command);
         }
     }
 }
 
-pub struct ReplayMessage {
-    pub topic: String,
-    pub payload: String,
-}
-
-pub struct PublishMessage {
-    pub topic: String,
-    pub payload: String,
+/// room presence changes to record, nothing is recorded while on vacation.
+/// Without a last status all rooms are recorded, so rooms which were present
+/// when light-control stopped are closed.
+fn get_history_entries(
+    last_status: Option<&EngineStatus>,
+    status: &EngineStatus,
+    timestamp: u64,
+) -> Vec<HistoryEntry> {
+    if status.vacation {
+        return vec![];
+    }
+    status
+        .rooms
+        .iter()
+        .filter(|(room, present)| match last_status {
+            Some(last_status) => last_status.rooms.get(*room) != Some(*present),
+            None => true,
+        })
+        .map(|(room, present)| HistoryEntry {
+            timestamp,
+            room: room.clone(),
+            present: *present,
+        })
+        .collect()
 }
 
-/// Object used to send messages to the main decision engine
-pub enum UpdateMessage {
-    /// Send a Scene change
-    /// * names of excluded topics
-    /// * brightness
-    SceneChange {
-        disabled_switches: Vec<String>,
-        enabled_switches: Vec<String>,
-        ignored_switches: Vec<String>,
-        brightness: u8,
-        enable_room_tracking: bool,
-        ignored_sensors: Vec<String>,
-    },
-    /// Send a State change
-    SwitchChange(Instant, SwitchChangeContent),
-    /// Send a State change
-    SensorChange(Instant, SensorChangeContent),
-    /// used to trigger regular calculation
-    Ping,
-    /// Deinit everything after a while
-    Deinit(Instant),
+/// update message for the action of a trigger
+fn get_trigger_update_message(
+    configuration: &Configuration,
+    trigger: &Trigger,
+) -> Option<UpdateMessage> {
+    info!("{} triggers {:?}", trigger.topic, trigger.action);
+    match &trigger.action {
+        TriggerAction::Scene(name) => configuration
+            .get_scene(name)
+            .map(|scene| UpdateMessage::SceneChange(scene.clone())),
+        TriggerAction::Cycle(names) => Some(UpdateMessage::SceneCycle(
+            names
+                .iter()
+                .filter_map(|name| configuration.get_scene(name))
+                .cloned()
+                .collect(),
+        )),
+        TriggerAction::ToggleRoomTracking => Some(UpdateMessage::RoomTrackingToggle),
+        TriggerAction::Brightness(brightness) => Some(UpdateMessage::BrightnessChange(*brightness)),
+    }
 }
 
-pub struct SwitchChangeContent {
+pub struct ReplayMessage {
     pub topic: String,
-    pub state: SwitchState,
+    pub payload: String,
 }
 
-pub struct SensorChangeContent {
-    pub topic: String,
-    pub state: SensorState,
+/// Messages for the publish thread
+pub enum PublishMessage {
+    /// command for a switch
+    Switch(SwitchCommand),
+    /// plain mqtt message
+    Raw {
+        topic: String,
+        payload: String,
+        qos: i32,
+        retained: bool,
+    },
 }
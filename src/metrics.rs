@@ -0,0 +1,269 @@
+//! Prometheus metrics
+
+use light_control::configuration::{Configuration, SwitchState};
+use light_control::engine::EngineStatus;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Default)]
+pub struct Metrics {
+    /// names of all scenes
+    scenes: Vec<String>,
+    /// mqtt messages received per topic
+    messages_received: BTreeMap<String, u64>,
+    /// unix timestamp of the last message per topic
+    last_message: BTreeMap<String, u64>,
+    /// messages per topic that couldn't be parsed
+    parse_failures: BTreeMap<String, u64>,
+    /// switch commands published per switch and state
+    switch_commands: BTreeMap<(String, String), u64>,
+    /// time spent handling messages in the engine
+    handle_duration: Duration,
+    handle_count: u64,
+    /// last known engine status
+    status: Option<EngineStatus>,
+}
+
+impl Metrics {
+    pub fn new(configuration: &Configuration) -> Self {
+        Metrics {
+            scenes: configuration
+                .scenes
+                .iter()
+                .map(|scene| scene.name.clone())
+                .collect(),
+            ..Metrics::default()
+        }
+    }
+
+    pub fn message_received(&mut self, topic: &str) {
+        *self.messages_received.entry(topic.to_string()).or_insert(0) += 1;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        self.last_message.insert(topic.to_string(), timestamp);
+    }
+
+    pub fn parse_failure(&mut self, topic: &str) {
+        *self.parse_failures.entry(topic.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn switch_command(&mut self, topic: &str, state: SwitchState) {
+        let key = (topic.to_string(), format!("{:?}", state));
+        *self.switch_commands.entry(key).or_insert(0) += 1;
+    }
+
+    pub fn handle(&mut self, duration: Duration) {
+        self.handle_duration += duration;
+        self.handle_count += 1;
+    }
+
+    pub fn set_status(&mut self, status: EngineStatus) {
+        self.status = Some(status);
+    }
+
+    /// prometheus text format
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        header(
+            &mut output,
+            "light_control_messages_received_total",
+            "counter",
+            "mqtt messages received per topic",
+        );
+        for (topic, count) in self.messages_received.iter() {
+            line(
+                &mut output,
+                "light_control_messages_received_total",
+                &[("topic", topic)],
+                count,
+            );
+        }
+        header(
+            &mut output,
+            "light_control_last_message_timestamp_seconds",
+            "gauge",
+            "unix timestamp of the last mqtt message per topic",
+        );
+        for (topic, timestamp) in self.last_message.iter() {
+            line(
+                &mut output,
+                "light_control_last_message_timestamp_seconds",
+                &[("topic", topic)],
+                timestamp,
+            );
+        }
+        header(
+            &mut output,
+            "light_control_parse_failures_total",
+            "counter",
+            "mqtt messages which couldn't be parsed per topic",
+        );
+        for (topic, count) in self.parse_failures.iter() {
+            line(
+                &mut output,
+                "light_control_parse_failures_total",
+                &[("topic", topic)],
+                count,
+            );
+        }
+        header(
+            &mut output,
+            "light_control_switch_commands_total",
+            "counter",
+            "switch commands published per switch and state",
+        );
+        for ((topic, state), count) in self.switch_commands.iter() {
+            line(
+                &mut output,
+                "light_control_switch_commands_total",
+                &[("switch", topic), ("state", state)],
+                count,
+            );
+        }
+        header(
+            &mut output,
+            "light_control_handle_seconds",
+            "summary",
+            "time spent handling a message, including calculating switch commands",
+        );
+        line(
+            &mut output,
+            "light_control_handle_seconds_sum",
+            &[],
+            self.handle_duration.as_secs_f64(),
+        );
+        line(
+            &mut output,
+            "light_control_handle_seconds_count",
+            &[],
+            self.handle_count,
+        );
+        if let Some(status) = &self.status {
+            header(
+                &mut output,
+                "light_control_room_presence",
+                "gauge",
+                "1 if the room is present",
+            );
+            for (room, present) in status.rooms.iter() {
+                line(
+                    &mut output,
+                    "light_control_room_presence",
+                    &[("room", room)],
+                    *present as u8,
+                );
+            }
+            header(
+                &mut output,
+                "light_control_current_room",
+                "gauge",
+                "1 for the current room",
+            );
+            for room in status.rooms.keys() {
+                let current = status.current_room.as_ref() == Some(room);
+                line(
+                    &mut output,
+                    "light_control_current_room",
+                    &[("room", room)],
+                    current as u8,
+                );
+            }
+            header(
+                &mut output,
+                "light_control_active_scene",
+                "gauge",
+                "1 for the active scene",
+            );
+            for scene in self.scenes.iter() {
+                let active = status.scene.as_ref() == Some(scene);
+                line(
+                    &mut output,
+                    "light_control_active_scene",
+                    &[("scene", scene)],
+                    active as u8,
+                );
+            }
+        }
+        output
+    }
+}
+
+fn header(output: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(output, "# HELP {} {}", name, help).unwrap();
+    writeln!(output, "# TYPE {} {}", name, kind).unwrap();
+}
+
+fn line<V: std::fmt::Display>(output: &mut String, name: &str, labels: &[(&str, &str)], value: V) {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+        .collect();
+    if labels.is_empty() {
+        writeln!(output, "{} {}", name, value).unwrap();
+    } else {
+        writeln!(output, "{}{{{}}} {}", name, labels.join(","), value).unwrap();
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut metrics = Metrics {
+            scenes: vec!["day".to_string(), "night".to_string()],
+            ..Metrics::default()
+        };
+        metrics.message_received("zigbee2mqtt/motion1");
+        metrics.message_received("zigbee2mqtt/motion1");
+        metrics.parse_failure("zigbee2mqtt/motion1");
+        metrics.switch_command("zigbee2mqtt/light1", SwitchState::On);
+        metrics.handle(Duration::from_millis(2));
+        let mut rooms = BTreeMap::new();
+        rooms.insert("bed_room".to_string(), true);
+        rooms.insert("kitchen".to_string(), false);
+        metrics.set_status(EngineStatus {
+            scene: Some("night".to_string()),
+            overlays: vec![],
+            current_room: Some("bed_room".to_string()),
+            rooms,
+            brightness: 255,
+            room_tracking: true,
+            switches: BTreeMap::new(),
+            switch_overrides: BTreeMap::new(),
+            stale_sensors: vec![],
+            unresponsive_switches: vec![],
+            away: false,
+            vacation: false,
+        });
+        let output = metrics.render();
+        assert!(output
+            .contains("light_control_messages_received_total{topic=\"zigbee2mqtt/motion1\"} 2\n"));
+        assert!(output
+            .contains("light_control_parse_failures_total{topic=\"zigbee2mqtt/motion1\"} 1\n"));
+        assert!(output.contains(
+            "light_control_switch_commands_total{switch=\"zigbee2mqtt/light1\",state=\"On\"} 1\n"
+        ));
+        assert!(output.contains("light_control_handle_seconds_count 1\n"));
+        assert!(output.contains("light_control_room_presence{room=\"kitchen\"} 0\n"));
+        assert!(output.contains("light_control_current_room{room=\"bed_room\"} 1\n"));
+        assert!(output.contains("light_control_active_scene{scene=\"day\"} 0\n"));
+        assert!(output.contains("light_control_active_scene{scene=\"night\"} 1\n"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -2,8 +2,9 @@
 //extern crate log;
 //extern crate paho_mqtt;
 
-use paho_mqtt::Client;
+use paho_mqtt::{Client, Message};
 use std::process;
+use std::sync::mpsc::Receiver;
 use std::time::Duration;
 
 pub struct MqttClient {
@@ -11,7 +12,12 @@ pub struct MqttClient {
 }
 
 impl MqttClient {
-    pub fn new(host: String, username: String, password: String, topics: Vec<&String>) -> Self {
+    pub fn new(
+        host: String,
+        username: String,
+        password: String,
+        subscriptions: Vec<(String, i32)>,
+    ) -> (Self, Receiver<Option<Message>>) {
         // Create the client. Use an ID for a persistent session.
         // A real system should try harder to use a unique ID.
         let create_opts = paho_mqtt::CreateOptionsBuilder::new()
@@ -20,7 +26,7 @@ impl MqttClient {
             .finalize();
 
         // Create the client connection
-        let cli = paho_mqtt::Client::new(create_opts).unwrap_or_else(|e| {
+        let mut cli = paho_mqtt::Client::new(create_opts).unwrap_or_else(|e| {
             error!("Error creating the client: {:?}", e);
             process::exit(1);
         });
@@ -37,6 +43,10 @@ impl MqttClient {
             .user_name(username)
             .finalize();
 
+        // consume before subscribing, so retained messages
+        // which are delivered right after subscribing are not lost
+        let receiver = cli.start_consuming();
+
         // Make the connection to the broker
         info!("Connecting to the MQTT server...");
         if let Err(err) = cli.connect(conn_opts) {
@@ -44,10 +54,10 @@ impl MqttClient {
             process::exit(1);
         }
 
-        for topic in topics {
-            cli.subscribe(topic.as_str(), 0).unwrap();
+        for (topic, qos) in subscriptions {
+            cli.subscribe(topic.as_str(), qos).unwrap();
         }
 
-        Self { cli }
+        (Self { cli }, receiver)
     }
 }
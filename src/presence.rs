@@ -0,0 +1,137 @@
+use crate::configuration::Configuration;
+use crate::engine::{DecisionEngine, EngineStatus};
+use crate::snapshot::Snapshot;
+use crate::strategy::{Strategy, SwitchCommand};
+use crate::UpdateMessage;
+use std::time::Instant;
+
+/// Room presence only, no current room tracking.
+/// Switches are on as long as one of their rooms is present.
+pub struct PresenceStrategy {
+    strategy: Strategy,
+}
+
+impl PresenceStrategy {
+    pub fn new(configuration: &Configuration) -> Self {
+        let mut strategy = Strategy::new(configuration);
+        strategy.set_room_tracking_available(false);
+        PresenceStrategy { strategy }
+    }
+}
+
+impl DecisionEngine for PresenceStrategy {
+    fn handle(&mut self, message: UpdateMessage) -> Vec<SwitchCommand> {
+        match message {
+            // like the room tracking strategy, without calculating the current room
+            UpdateMessage::Ping => {}
+            UpdateMessage::SensorChange(instant, sensor_content) => {
+                self.strategy.update_sensor(instant, sensor_content);
+            }
+            UpdateMessage::OccupantsChange(_) => {
+                debug!("occupants are not tracked by the presence strategy");
+            }
+            UpdateMessage::RoomTrackingChange(_) | UpdateMessage::RoomTrackingToggle => {
+                debug!("room tracking is always disabled in the presence strategy");
+            }
+            message => return self.strategy.handle(message),
+        }
+        self.strategy.on_tick(Instant::now(), false, false)
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.strategy.next_deadline()
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        self.strategy.snapshot()
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot, configuration: &Configuration) {
+        self.strategy.restore(snapshot, configuration);
+    }
+
+    fn status(&self) -> EngineStatus {
+        self.strategy.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::{SensorState, StrategyKind, SwitchState};
+    use crate::dummy_configuration::{create_light_switch, create_motion_sensor, hardcoded_config};
+    use crate::engine::create_engine;
+    use crate::{SensorChangeContent, SwitchChangeContent};
+    use std::time::Duration;
+
+    fn create_configuration(strategy: StrategyKind) -> Configuration {
+        Configuration {
+            scenes: vec![],
+            sensors: vec![
+                create_motion_sensor("motion1", "room1".to_string()),
+                create_motion_sensor("motion2", "room2".to_string()),
+            ],
+            switches: vec![create_light_switch("light1", vec!["room1".to_string()])],
+            strategy,
+            ..hardcoded_config()
+        }
+    }
+
+    fn sensor_change(topic: &str, seconds_ago: u64, state: SensorState) -> UpdateMessage {
+        UpdateMessage::SensorChange(
+            Instant::now() - Duration::from_secs(seconds_ago),
+            SensorChangeContent {
+                topic: topic.to_string(),
+                state,
+            },
+        )
+    }
+
+    fn get_switch_states(strategy: StrategyKind) -> Vec<SwitchState> {
+        let mut engine = create_engine(&create_configuration(strategy));
+        let mut states = Vec::new();
+        for message in [
+            sensor_change("motion2", 300, SensorState::Absent),
+            sensor_change("motion1", 200, SensorState::Present),
+            UpdateMessage::Ping,
+            sensor_change("motion1", 120, SensorState::Absent),
+            UpdateMessage::Ping,
+        ] {
+            for command in engine.handle(message) {
+                states.push(command.state);
+                // the switch confirms the new state
+                let content = SwitchChangeContent {
+                    topic: command.topic,
+                    state: command.state,
+                };
+                engine.handle(UpdateMessage::SwitchChange(Instant::now(), content));
+            }
+        }
+        states
+    }
+
+    #[test]
+    fn test_room_tracking_keeps_current_room_on() {
+        assert_eq!(
+            get_switch_states(StrategyKind::RoomTracking),
+            vec![SwitchState::On]
+        );
+    }
+
+    #[test]
+    fn test_presence_turns_absent_room_off() {
+        assert_eq!(
+            get_switch_states(StrategyKind::Presence),
+            vec![SwitchState::On, SwitchState::Off]
+        );
+    }
+
+    #[test]
+    fn test_presence_has_no_current_room() {
+        let mut engine = create_engine(&create_configuration(StrategyKind::Presence));
+        engine.handle(sensor_change("motion2", 300, SensorState::Absent));
+        engine.handle(sensor_change("motion1", 10, SensorState::Present));
+        engine.handle(UpdateMessage::Ping);
+        assert_eq!(engine.status().current_room, None);
+    }
+}
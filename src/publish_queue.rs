@@ -0,0 +1,203 @@
+//! Rate limited queue for switch commands
+
+use light_control::configuration::RateLimit;
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Allows `rate` events per second, with bursts of up to `capacity`
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        let capacity = rate.max(1.0);
+        TokenBucket {
+            rate,
+            capacity,
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// when the next token is available
+    fn ready_at(&self) -> Instant {
+        if self.tokens >= 1.0 {
+            self.updated
+        } else {
+            self.updated + Duration::from_secs_f64((1.0 - self.tokens) / self.rate)
+        }
+    }
+}
+
+/// Queue of messages per switch.
+/// A newer message for a queued switch replaces the queued one,
+/// so only the latest command of a switch is sent.
+/// Rate limit prefixes match the command topic.
+pub struct PublishQueue<T> {
+    /// switch topic, command topic and message
+    queue: VecDeque<(String, String, T)>,
+    global: Option<TokenBucket>,
+    prefixes: Vec<(String, TokenBucket)>,
+}
+
+impl<T> PublishQueue<T> {
+    pub fn new(rate_limit: Option<&RateLimit>, now: Instant) -> Self {
+        let global = rate_limit
+            .and_then(|rate_limit| rate_limit.commands_per_second)
+            .map(|rate| TokenBucket::new(rate, now));
+        let mut prefixes = Vec::new();
+        if let Some(rate_limit) = rate_limit {
+            for prefix in rate_limit.prefixes.iter() {
+                prefixes.push((
+                    prefix.prefix.clone(),
+                    TokenBucket::new(prefix.commands_per_second, now),
+                ));
+            }
+        }
+        // longest prefix first
+        prefixes.sort_by_key(|(prefix, _)| Reverse(prefix.len()));
+        PublishQueue {
+            queue: VecDeque::new(),
+            global,
+            prefixes,
+        }
+    }
+
+    /// queue a message, or replace the queued message of the switch
+    pub fn push(&mut self, switch: String, topic: String, message: T) {
+        match self
+            .queue
+            .iter_mut()
+            .find(|(queued, _, _)| *queued == switch)
+        {
+            Some(queued) => {
+                debug!("coalesce queued command for {}", switch);
+                queued.1 = topic;
+                queued.2 = message;
+            }
+            None => self.queue.push_back((switch, topic, message)),
+        }
+    }
+
+    fn get_prefix_index(&self, topic: &str) -> Option<usize> {
+        self.prefixes
+            .iter()
+            .position(|(prefix, _)| topic.starts_with(prefix.as_str()))
+    }
+
+    /// next message which is allowed to be sent now
+    pub fn pop(&mut self, now: Instant) -> Option<T> {
+        if let Some(global) = self.global.as_mut() {
+            global.refill(now);
+            if global.tokens < 1.0 {
+                return None;
+            }
+        }
+        for (_, bucket) in self.prefixes.iter_mut() {
+            bucket.refill(now);
+        }
+        for index in 0..self.queue.len() {
+            if let Some(prefix_index) = self.get_prefix_index(&self.queue[index].1) {
+                let bucket = &mut self.prefixes[prefix_index].1;
+                if bucket.tokens < 1.0 {
+                    continue;
+                }
+                bucket.tokens -= 1.0;
+            }
+            if let Some(global) = self.global.as_mut() {
+                global.tokens -= 1.0;
+            }
+            return self.queue.remove(index).map(|(_, _, message)| message);
+        }
+        None
+    }
+
+    /// when the next queued message can be sent, None if the queue is empty
+    pub fn next_ready(&self) -> Option<Instant> {
+        let global_ready = self.global.as_ref().map(|global| global.ready_at());
+        self.queue
+            .iter()
+            .map(|(_, topic, _)| {
+                let prefix_ready = self
+                    .get_prefix_index(topic)
+                    .map(|index| self.prefixes[index].1.ready_at());
+                match (global_ready, prefix_ready) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    (a, b) => a.or(b),
+                }
+            })
+            .map(|ready| ready.unwrap_or_else(Instant::now))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use light_control::configuration::PrefixRateLimit;
+
+    fn push(queue: &mut PublishQueue<u8>, switch: &str, message: u8) {
+        queue.push(switch.to_string(), format!("{}/set", switch), message);
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let now = Instant::now();
+        let mut queue = PublishQueue::new(None, now);
+        push(&mut queue, "zigbee2mqtt/light_1", 1);
+        push(&mut queue, "zigbee2mqtt/light_2", 2);
+        push(&mut queue, "zigbee2mqtt/light_1", 3);
+        // two endpoints of a device share the command topic
+        queue.push("endpoint_1".to_string(), "cmnd/device".to_string(), 4);
+        queue.push("endpoint_2".to_string(), "cmnd/device".to_string(), 5);
+        assert_eq!(queue.pop(now), Some(3));
+        assert_eq!(queue.pop(now), Some(2));
+        assert_eq!(queue.pop(now), Some(4));
+        assert_eq!(queue.pop(now), Some(5));
+        assert_eq!(queue.pop(now), None);
+        assert_eq!(queue.next_ready(), None);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let now = Instant::now();
+        let rate_limit = RateLimit {
+            commands_per_second: Some(2.0),
+            prefixes: vec![PrefixRateLimit {
+                prefix: "zigbee2mqtt/".to_string(),
+                commands_per_second: 1.0,
+            }],
+        };
+        let mut queue = PublishQueue::new(Some(&rate_limit), now);
+        push(&mut queue, "zigbee2mqtt/light_1", 1);
+        push(&mut queue, "zigbee2mqtt/light_2", 2);
+        queue.push("stat/PAL01".to_string(), "cmnd/PAL01/POWER".to_string(), 3);
+        queue.push("stat/PAL03".to_string(), "cmnd/PAL03/POWER".to_string(), 4);
+
+        assert_eq!(queue.pop(now), Some(1));
+        // zigbee2mqtt/ is limited, other topics can be sent
+        assert_eq!(queue.pop(now), Some(3));
+        // global limit is reached
+        assert_eq!(queue.pop(now), None);
+        assert_eq!(queue.next_ready(), Some(now + Duration::from_millis(500)));
+
+        let later = now + Duration::from_millis(500);
+        assert_eq!(queue.pop(later), Some(4));
+        assert_eq!(queue.pop(later), None);
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(queue.pop(later), Some(2));
+        assert_eq!(queue.next_ready(), None);
+    }
+}
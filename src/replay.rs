@@ -1,7 +1,7 @@
 //! to create replay scripts that can be used for integration tests
 //! or debugging false behavior
 
-use crate::configuration::Configuration;
+use light_control::configuration::Configuration;
 use std::error::Error;
 use std::fs::File;
 use std::io::Write;
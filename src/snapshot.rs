@@ -0,0 +1,162 @@
+//! runtime state which survives a restart
+
+use crate::configuration::SwitchState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Snapshot of the engine state.
+/// All timestamps are seconds since the unix epoch.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Snapshot {
+    /// when the snapshot was taken
+    pub timestamp: u64,
+    /// name of the active scene
+    pub scene: Option<String>,
+    /// names of the overlays stacked on top of the scene
+    #[serde(default)]
+    pub overlays: Vec<String>,
+    pub current_room: Option<String>,
+    #[serde(default)]
+    pub current_rooms: Vec<String>,
+    pub occupants: usize,
+    /// brightness, if changed after the scene was set
+    #[serde(default)]
+    pub brightness: Option<u8>,
+    /// room tracking, if changed after the scene was set
+    #[serde(default)]
+    pub room_tracking: Option<bool>,
+    /// last known sensor states by topic, uninitialized sensors are left out
+    #[serde(default)]
+    pub sensors: HashMap<String, SensorSnapshot>,
+    /// last known switch states by topic
+    #[serde(default)]
+    pub switches: HashMap<String, SwitchState>,
+    /// manual overrides by switch topic
+    #[serde(default)]
+    pub switch_overrides: HashMap<String, SwitchState>,
+    /// the whole home is absent
+    #[serde(default)]
+    pub away: bool,
+    /// the presence simulation is running
+    #[serde(default)]
+    pub vacation: bool,
+    /// name of the scene which is restored after away
+    #[serde(default)]
+    pub scene_before_away: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorSnapshot {
+    Present,
+    AbsentSince(u64),
+}
+
+impl Snapshot {
+    pub fn load_from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        let snapshot = serde_json::from_reader(reader)?;
+        Ok(snapshot)
+    }
+
+    /// write to a temporary file first, so a crash never leaves a broken state file
+    pub fn save_to_file(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let temporary_path = path.with_extension("tmp");
+        let file = File::create(&temporary_path)?;
+        serde_json::to_writer(BufWriter::new(file), self)?;
+        std::fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+
+    /// equal, apart from when the snapshots were taken
+    pub fn is_same_state(&self, other: &Snapshot) -> bool {
+        let snapshot = Snapshot {
+            timestamp: other.timestamp,
+            ..self.clone()
+        };
+        snapshot == *other
+    }
+
+    /// how old the snapshot is
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(now_timestamp().saturating_sub(self.timestamp))
+    }
+}
+
+pub fn now_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// wall clock timestamp of an instant
+pub fn to_timestamp(instant: Instant) -> u64 {
+    now_timestamp().saturating_sub(instant.elapsed().as_secs())
+}
+
+/// instant of a wall clock timestamp, None if it is too far in the past
+pub fn to_instant(timestamp: u64) -> Option<Instant> {
+    let age = Duration::from_secs(now_timestamp().saturating_sub(timestamp));
+    Instant::now().checked_sub(age)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_round_trip() {
+        let instant = Instant::now() - Duration::from_secs(60);
+        let restored = to_instant(to_timestamp(instant)).unwrap();
+        assert!(restored.elapsed() >= Duration::from_secs(59));
+        assert!(restored.elapsed() <= Duration::from_secs(61));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut sensors = HashMap::new();
+        sensors.insert("motion1".to_string(), SensorSnapshot::AbsentSince(1000));
+        let mut switches = HashMap::new();
+        switches.insert("light1".to_string(), SwitchState::On);
+        let snapshot = Snapshot {
+            timestamp: now_timestamp() - 30,
+            scene: Some("night".to_string()),
+            overlays: vec!["movie".to_string()],
+            current_room: Some("room1".to_string()),
+            current_rooms: vec![],
+            occupants: 1,
+            brightness: Some(100),
+            room_tracking: None,
+            sensors,
+            switches,
+            switch_overrides: HashMap::new(),
+            away: true,
+            vacation: false,
+            scene_before_away: Some("day".to_string()),
+        };
+        let path = std::env::temp_dir().join("light-control-test-state.json");
+        snapshot.save_to_file(&path).unwrap();
+        let loaded = Snapshot::load_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, snapshot);
+        assert!(loaded.age() >= Duration::from_secs(30));
+
+        let later = Snapshot {
+            timestamp: snapshot.timestamp + 10,
+            ..snapshot.clone()
+        };
+        assert!(later.is_same_state(&snapshot));
+        let changed = Snapshot {
+            occupants: 2,
+            ..later
+        };
+        assert!(!changed.is_same_state(&snapshot));
+    }
+}
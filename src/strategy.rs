@@ -1,25 +1,40 @@
+mod room_graph;
 mod room_state;
 mod sensor_memory;
 mod sensor_states;
 
-use crate::configuration::{Configuration, SensorState, SwitchState};
+use crate::configuration::{
+    Away, Circadian, Color, Configuration, LightSettings, Overlay, Scene, SensorState,
+    StartupPolicy, SwitchState,
+};
+use crate::engine::{DecisionEngine, EngineStatus};
+use crate::history::History;
+use crate::snapshot::{now_timestamp, to_instant, to_timestamp, SensorSnapshot, Snapshot};
+use crate::strategy::room_graph::RoomGraph;
 use crate::strategy::room_state::RoomState;
 use crate::strategy::sensor_memory::SensorMemory;
 use crate::strategy::sensor_states::{SensorMemoryNaiveState, SensorMemoryState};
-use crate::{SensorChangeContent, SwitchChangeContent};
+use crate::{SensorChangeContent, SwitchChangeContent, UpdateMessage};
+use chrono::Timelike;
 use std::collections::{BTreeSet, HashMap};
 use std::iter::FromIterator;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
 type Topic = String;
 type Room = String;
 type Sensors = HashMap<Topic, SensorMemory>;
 
-#[derive(Debug, PartialEq)]
+/// the retry backoff doesn't grow beyond 2^10 times the confirm timeout
+const MAX_BACKOFF_EXPONENT: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct SwitchCommand {
     pub topic: String,
     pub state: SwitchState,
     pub brightness: u8,
+    pub color_temp: Option<u16>,
+    pub color: Option<Color>,
 }
 
 pub struct SwitchMemory {
@@ -27,6 +42,38 @@ pub struct SwitchMemory {
     pub state: SwitchState,
     pub rooms: Vec<String>,
     pub delay: Duration,
+    pub color_support: bool,
+    /// dim the switch this long before it is turned off
+    pub dim_before_off: Duration,
+    pub dim_brightness: u8,
+    /// last brightness that was commanded
+    pub brightness: Option<u8>,
+    /// last color temperature that was commanded
+    pub color_temp: Option<u16>,
+    /// how long the switch has to confirm a command, None disables confirmation
+    pub confirm_timeout: Option<Duration>,
+    pub max_retries: u32,
+    /// last command which is not confirmed yet
+    pub pending: Option<PendingCommand>,
+}
+
+impl SwitchMemory {
+    /// the switch didn't confirm the last command after all retries
+    pub fn is_unresponsive(&self) -> bool {
+        match &self.pending {
+            Some(pending) => pending.retry_at.is_none(),
+            None => false,
+        }
+    }
+}
+
+/// Command which is not confirmed by the switch yet
+pub struct PendingCommand {
+    pub command: SwitchCommand,
+    /// how often the command was sent again
+    pub retries: u32,
+    /// when to send the command again, None if the switch is unresponsive
+    pub retry_at: Option<Instant>,
 }
 
 pub struct Strategy {
@@ -42,15 +89,55 @@ pub struct Strategy {
     /// room we think the user is located
     current_room: Option<Room>,
 
+    /// number of people we track
+    occupants: usize,
+
+    /// rooms we think the occupants are located, if there is more than one occupant
+    current_rooms: Vec<Room>,
+
+    /// how rooms are connected, to reject impossible current room transitions
+    room_graph: RoomGraph,
+
+    /// since when rooms are present, to allow handovers to non adjacent rooms
+    present_since: HashMap<Room, Instant>,
+
     /// switch topics which should be permanent disabled
     disabled_switches: Vec<String>,
     /// switch topics which should be permanent enabled
     enabled_switches: Vec<String>,
     /// switch topics which should be ignored
     ignored_switches: Vec<String>,
+    /// switch states set manually, until the next scene change
+    switch_overrides: HashMap<Topic, SwitchState>,
+
+    /// name of the active scene
+    scene: Option<String>,
+    /// active scene without overlays, to remove overlays again
+    base_scene: Option<Scene>,
+    /// overlays stacked on top of the active scene
+    overlays: Vec<Overlay>,
+    /// brightness set manually, until the next scene change
+    manual_brightness: Option<u8>,
+    /// room tracking set manually, until the next scene change
+    manual_room_tracking: Option<bool>,
 
     /// current brightness
     brightness: u8,
+    /// current color temperature
+    color_temp: Option<u16>,
+    /// current color
+    color: Option<Color>,
+    /// brightness and color temperature per room
+    room_settings: HashMap<Room, LightSettings>,
+    /// brightness and color temperature per switch topic
+    switch_settings: HashMap<Topic, LightSettings>,
+
+    /// brightness and color temperature curve over the day
+    circadian: Option<Circadian>,
+    /// weather or not the current scene follows the circadian curve
+    circadian_enabled: bool,
+    /// last time the circadian curve was applied
+    circadian_updated: Option<Instant>,
 
     /// min possible delay of all sensors, to look in the future and
     /// determine the current_room
@@ -61,27 +148,70 @@ pub struct Strategy {
     /// it must be shorter by the factor of this factor
     current_room_threshold: Duration,
 
+    /// rooms whose transit sensor (e.g. a door) fired and which had no
+    /// presence since, they were most likely left
+    left_rooms: BTreeSet<Room>,
+
     /// weather or not current_room should stay on or not
     room_tracking_enabled: bool,
+    /// room tracking can't be enabled, if false
+    room_tracking_available: bool,
 
     /// ignore these sensors
     ignored_sensors: Vec<String>,
+
+    /// sensors which didn't send a message for longer than their heartbeat
+    stale_sensors: BTreeSet<Topic>,
+
+    /// when the strategy was started, to take over uninitialized sensors
+    started: Instant,
+
+    /// away mode settings
+    away_config: Option<Away>,
+    /// scene to activate while away
+    away_scene: Option<Scene>,
+    /// weather or not the whole home is absent
+    away: bool,
+    /// scene which was active before away, restored on presence
+    scene_before_away: Option<Scene>,
+    /// last time one of the away doors was opened
+    last_door_open: Option<Instant>,
+    /// last time a non transit sensor became present
+    last_motion: Option<Instant>,
+
+    /// recorded presence, replayed while on vacation
+    vacation: Option<History>,
+    /// rooms which are present because of the presence simulation
+    simulated_rooms: BTreeSet<Room>,
 }
 
 impl Strategy {
     /// create a new StateMemory object out of a Configuration
     pub fn new(configuration: &Configuration) -> Self {
+        let started = Instant::now();
         let mut room_sensors = HashMap::new();
         for sensor in configuration.sensors.iter() {
             if !room_sensors.contains_key(&sensor.room) {
                 room_sensors.insert(sensor.room.clone(), HashMap::new());
             }
             let sensors_memory = room_sensors.get_mut(&sensor.room).unwrap();
+            let delay = Duration::from_secs(sensor.delay);
+            let state = match configuration.startup_policy {
+                StartupPolicy::Wait => SensorMemoryState::Uninitialized,
+                // absent long enough, so the rooms are absent right away
+                StartupPolicy::AssumeAbsent => {
+                    SensorMemoryState::AbsentSince(started.checked_sub(delay).unwrap_or(started))
+                }
+            };
             sensors_memory.insert(
                 sensor.topic.clone(),
                 SensorMemory {
-                    delay: Duration::from_secs(sensor.delay),
-                    state: SensorMemoryState::Uninitialized,
+                    delay,
+                    state,
+                    kind: sensor.kind,
+                    heartbeat: sensor.heartbeat.map(Duration::from_secs),
+                    ignore_when_stale: sensor.ignore_when_stale,
+                    last_seen: started,
                 },
             );
             info!(
@@ -96,6 +226,14 @@ impl Strategy {
                 state: SwitchState::Unknown,
                 rooms: switch.rooms.clone(),
                 delay: Duration::from_secs(switch.delay),
+                color_support: switch.color_support,
+                dim_before_off: Duration::from_secs(switch.dim_before_off),
+                dim_brightness: switch.dim_brightness,
+                brightness: None,
+                color_temp: None,
+                confirm_timeout: switch.confirm_timeout.map(Duration::from_secs),
+                max_retries: switch.max_retries,
+                pending: None,
             });
         }
         let look_ahead = configuration.get_min_sensor_delay();
@@ -109,76 +247,169 @@ impl Strategy {
             warn!("look ahead is smaller than current room threshold, lights will be turned off before current room detections is calculated")
         }
 
-        let (
-            brightness,
-            disabled_switches,
-            enabled_switches,
-            ignored_switches,
-            room_tracking_enabled,
-            ignored_sensors,
-        ) = configuration
-            .scenes
-            .get(0)
-            .map(|default_scene| {
-                (
-                    default_scene.brightness.clone(),
-                    default_scene.disabled_switches.clone(),
-                    default_scene.enabled_switches.clone(),
-                    default_scene.ignored_switches.clone(),
-                    default_scene.room_tracking_enabled.clone(),
-                    default_scene.ignored_sensors.clone(),
-                )
-            })
-            .unwrap_or((255, vec![], vec![], vec![], true, vec![]));
-
-        Strategy {
+        let away_scene = configuration
+            .away
+            .as_ref()
+            .and_then(|away| away.scene.as_ref())
+            .and_then(|name| {
+                let scene = configuration.get_scene(name);
+                if scene.is_none() {
+                    warn!("away scene {} is unknown", name);
+                }
+                scene.cloned()
+            });
+
+        let mut strategy = Strategy {
             room_sensors,
             room_switches,
             look_ahead: Duration::from_secs(look_ahead),
             room_state: HashMap::new(),
             current_room: None,
-            disabled_switches,
-            enabled_switches,
-            ignored_switches,
-            brightness,
+            occupants: configuration.occupants,
+            current_rooms: Vec::new(),
+            room_graph: RoomGraph::new(&configuration.rooms),
+            present_since: HashMap::new(),
+            disabled_switches: vec![],
+            enabled_switches: vec![],
+            ignored_switches: vec![],
+            switch_overrides: HashMap::new(),
+            scene: None,
+            base_scene: None,
+            overlays: vec![],
+            manual_brightness: None,
+            manual_room_tracking: None,
+            brightness: 255,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
             current_room_threshold: Duration::from_secs(current_room_threshold),
-            room_tracking_enabled,
-            ignored_sensors,
+            left_rooms: BTreeSet::new(),
+            room_tracking_enabled: true,
+            room_tracking_available: true,
+            ignored_sensors: vec![],
+            circadian: configuration.circadian.clone(),
+            circadian_enabled: false,
+            circadian_updated: None,
+            stale_sensors: BTreeSet::new(),
+            started,
+            away_config: configuration.away.clone(),
+            away_scene,
+            away: false,
+            scene_before_away: None,
+            last_door_open: None,
+            last_motion: None,
+            vacation: None,
+            simulated_rooms: BTreeSet::new(),
+        };
+        if let Some(default_scene) = configuration.scenes.first() {
+            strategy.set_scene(default_scene);
         }
+        strategy
     }
 
-    /// after some time none of the sensors can stay on the Initialized state
-    pub fn replace_uninitialized_with_absents(&mut self, instant: Instant) {
-        info!("takeover: all uninitialized sensors set to absent and all uninitialized switches will be turned off");
-        for sensor in self.room_sensors.values_mut() {
-            for sensor_state in sensor.values_mut() {
-                if sensor_state.state == SensorMemoryState::Uninitialized {
-                    sensor_state.state = SensorMemoryState::AbsentSince(instant.clone());
+    /// after their delay none of the sensors can stay on the Uninitialized state.
+    /// every sensor is taken over on its own, so switches of
+    /// uninitialized rooms will be turned off
+    pub fn take_over_uninitialized_sensors(&mut self) {
+        for (room, room_sensors) in self.room_sensors.iter_mut() {
+            for (topic, sensor_memory) in room_sensors.iter_mut() {
+                if sensor_memory.state == SensorMemoryState::Uninitialized
+                    && self.started.elapsed() >= sensor_memory.delay
+                {
+                    info!("takeover: {} in {} set to absent", topic, room);
+                    sensor_memory.state = SensorMemoryState::AbsentSince(self.started);
                 }
             }
         }
     }
 
-    pub fn update_sensor(&mut self, instant: Instant, sensor_content: SensorChangeContent) {
+    /// a sensor sent a message, so it is not stale
+    pub fn update_sensor_heartbeat(&mut self, instant: Instant, topic: &str) {
         for room in self.room_sensors.values_mut() {
-            room.get_mut(&sensor_content.topic).map(|sensor_memory| {
-                match (&sensor_memory.state, sensor_content.state) {
-                    (SensorMemoryState::Uninitialized, SensorState::Absent) => {
-                        sensor_memory.state = SensorMemoryState::AbsentSince(instant);
-                    }
-                    (SensorMemoryState::Uninitialized, SensorState::Present) => {
-                        sensor_memory.state = SensorMemoryState::Present
-                    }
-                    (SensorMemoryState::AbsentSince(_), SensorState::Absent) => (),
-                    (SensorMemoryState::AbsentSince(_), SensorState::Present) => {
-                        sensor_memory.state = SensorMemoryState::Present
-                    }
-                    (SensorMemoryState::Present, SensorState::Absent) => {
-                        sensor_memory.state = SensorMemoryState::AbsentSince(instant)
-                    }
-                    (SensorMemoryState::Present, SensorState::Present) => (),
+            if let Some(sensor_memory) = room.get_mut(topic) {
+                sensor_memory.last_seen = instant;
+            }
+        }
+    }
+
+    /// log sensors which became stale or are back again
+    pub fn update_stale_sensors(&mut self) {
+        let mut stale_sensors = BTreeSet::new();
+        for room_sensors in self.room_sensors.values() {
+            for (topic, sensor_memory) in room_sensors.iter() {
+                if sensor_memory.is_stale() {
+                    stale_sensors.insert(topic.clone());
                 }
-            });
+            }
+        }
+        for topic in stale_sensors.difference(&self.stale_sensors) {
+            warn!("{} is stale, no message since its heartbeat", topic);
+        }
+        for topic in self.stale_sensors.difference(&stale_sensors) {
+            info!("{} is back again", topic);
+        }
+        self.stale_sensors = stale_sensors;
+    }
+
+    pub fn update_sensor(&mut self, instant: Instant, sensor_content: SensorChangeContent) {
+        self.update_sensor_heartbeat(instant, &sensor_content.topic);
+        if sensor_content.state == SensorState::Present {
+            if let Some(away) = &self.away_config {
+                if away.doors.contains(&sensor_content.topic) {
+                    self.last_door_open = Some(instant);
+                }
+            }
+            let is_motion =
+                self.room_sensors
+                    .values()
+                    .any(|room| match room.get(&sensor_content.topic) {
+                        Some(sensor_memory) => !sensor_memory.kind.is_transit(),
+                        None => false,
+                    });
+            if is_motion {
+                self.last_motion = Some(instant);
+            }
+        }
+        let is_ignored = self.ignored_sensors.contains(&sensor_content.topic);
+        for (room, room_sensors) in self.room_sensors.iter_mut() {
+            let sensor_memory = match room_sensors.get_mut(&sensor_content.topic) {
+                Some(sensor_memory) => sensor_memory,
+                None => continue,
+            };
+            if sensor_content.state == SensorState::Present && !is_ignored {
+                // a transit sensor without presence afterwards is evidence of leaving
+                if sensor_memory.kind.is_transit() {
+                    self.left_rooms.insert(room.clone());
+                } else {
+                    self.left_rooms.remove(room);
+                }
+            }
+            if sensor_memory.kind.is_transit() {
+                // transit sensors only mark presents for their delay
+                if sensor_content.state == SensorState::Present
+                    || sensor_memory.state == SensorMemoryState::Uninitialized
+                {
+                    sensor_memory.state = SensorMemoryState::AbsentSince(instant);
+                }
+                continue;
+            }
+            match (&sensor_memory.state, sensor_content.state) {
+                (SensorMemoryState::Uninitialized, SensorState::Absent) => {
+                    sensor_memory.state = SensorMemoryState::AbsentSince(instant);
+                }
+                (SensorMemoryState::Uninitialized, SensorState::Present) => {
+                    sensor_memory.state = SensorMemoryState::Present
+                }
+                (SensorMemoryState::AbsentSince(_), SensorState::Absent) => (),
+                (SensorMemoryState::AbsentSince(_), SensorState::Present) => {
+                    sensor_memory.state = SensorMemoryState::Present
+                }
+                (SensorMemoryState::Present, SensorState::Absent) => {
+                    sensor_memory.state = SensorMemoryState::AbsentSince(instant)
+                }
+                (SensorMemoryState::Present, SensorState::Present) => (),
+            }
         }
     }
 
@@ -188,13 +419,174 @@ impl Strategy {
                 continue;
             }
             room_switch.state = switch_content.state;
+            let confirmed = match &room_switch.pending {
+                Some(pending) => pending.command.state == switch_content.state,
+                None => false,
+            };
+            if confirmed {
+                if room_switch.is_unresponsive() {
+                    info!("{} is responsive again", room_switch.topic);
+                }
+                room_switch.pending = None;
+            }
             break;
         }
     }
 
+    /// send unconfirmed commands again, with an exponential backoff.
+    /// switches which don't confirm after all retries are flagged unresponsive.
+    pub fn retry_commands(&mut self, now: Instant) -> Vec<SwitchCommand> {
+        let mut commands = Vec::new();
+        for switch in self.room_switches.iter_mut() {
+            let timeout = match switch.confirm_timeout {
+                Some(timeout) => timeout,
+                None => continue,
+            };
+            let pending = match switch.pending.as_mut() {
+                Some(pending) => pending,
+                None => continue,
+            };
+            match pending.retry_at {
+                Some(retry_at) if retry_at <= now => {}
+                _ => continue,
+            }
+            if pending.retries >= switch.max_retries {
+                warn!(
+                    "{} didn't confirm {:?} after {} retries, it is unresponsive",
+                    switch.topic, pending.command.state, pending.retries
+                );
+                pending.retry_at = None;
+                continue;
+            }
+            pending.retries += 1;
+            // capped, so many retries can't overflow
+            let backoff = 2u32.pow(pending.retries.min(MAX_BACKOFF_EXPONENT));
+            pending.retry_at = Some(now + timeout * backoff);
+            warn!(
+                "{} didn't confirm {:?}, retry {}/{}",
+                switch.topic, pending.command.state, pending.retries, switch.max_retries
+            );
+            commands.push(pending.command.clone());
+        }
+        commands
+    }
+
     pub fn calculate_current_room(&mut self) {
-        let rooms = self.get_room_state(self.look_ahead);
-        // prepare sorted_rooms list
+        let rooms = self.get_tracking_room_state(self.look_ahead);
+        self.update_present_since(&rooms);
+        let pinned_rooms = self.get_pinned_rooms();
+        if self.occupants > 1 {
+            self.calculate_current_rooms(&rooms, pinned_rooms);
+            return;
+        }
+        if !pinned_rooms.is_empty() {
+            if self
+                .current_room
+                .as_ref()
+                .is_some_and(|room| pinned_rooms.contains(room))
+            {
+                return;
+            }
+            self.current_room = pinned_rooms.first().cloned();
+            debug!(
+                "because a sticky sensor is present, current_room : {:?}",
+                self.current_room
+            );
+            return;
+        }
+        self.calculate_single_current_room(&rooms);
+    }
+
+    /// rooms which contain a present sticky sensor (e.g. bed or chair)
+    fn get_pinned_rooms(&self) -> Vec<Room> {
+        let mut pinned_rooms = Vec::new();
+        for (room, room_sensors) in self.room_sensors.iter() {
+            let is_pinned = room_sensors.iter().any(|(topic, sensor_memory)| {
+                sensor_memory.kind.is_sticky()
+                    && !self.ignored_sensors.contains(topic)
+                    && sensor_memory.is_present()
+            });
+            if is_pinned {
+                pinned_rooms.push(room.clone());
+            }
+        }
+        pinned_rooms.sort();
+        pinned_rooms
+    }
+
+    /// with more than one occupant, every occupant moves on from its current room
+    /// like a single occupant. Free slots take the most recently present rooms.
+    fn calculate_current_rooms(
+        &mut self,
+        rooms: &HashMap<Room, SensorMemoryNaiveState>,
+        pinned_rooms: Vec<Room>,
+    ) {
+        let sorted_rooms = Strategy::sort_rooms(rooms);
+        let mut current_rooms = pinned_rooms;
+        for from in self.current_rooms.iter() {
+            if current_rooms.len() >= self.occupants || current_rooms.contains(from) {
+                continue;
+            }
+            let from_state = match rooms.get(from) {
+                Some(SensorMemoryNaiveState::Uninitialized) | None => continue,
+                Some(from_state) => from_state,
+            };
+            // rooms of other occupants are not taken over
+            let next_room = sorted_rooms.iter().find(|room_state| {
+                !current_rooms.contains(&room_state.room)
+                    && !self.current_rooms.contains(&room_state.room)
+                    && self.is_moved_on(from, from_state, room_state, rooms)
+            });
+            match next_room {
+                Some(room_state) => current_rooms.push(room_state.room.clone()),
+                None => current_rooms.push(from.clone()),
+            }
+        }
+        for room_state in sorted_rooms.iter() {
+            if room_state.state == SensorMemoryNaiveState::Uninitialized
+                || current_rooms.contains(&room_state.room)
+            {
+                continue;
+            }
+            current_rooms.push(room_state.room.clone());
+        }
+        current_rooms.truncate(self.occupants);
+        if current_rooms != self.current_rooms {
+            debug!(
+                "because {} occupants are tracked, current_rooms : {:?}",
+                self.occupants, current_rooms
+            );
+        }
+        self.current_room = current_rooms.first().cloned();
+        self.current_rooms = current_rooms;
+    }
+
+    /// the same rules as for a single occupant: a present room takes over an absent room,
+    /// a shorter absent room only once `from` is absent longer than the threshold or was left.
+    fn is_moved_on(
+        &self,
+        from: &Room,
+        from_state: &SensorMemoryNaiveState,
+        to: &RoomState,
+        rooms: &HashMap<Room, SensorMemoryNaiveState>,
+    ) -> bool {
+        let is_left = match (from_state, &to.state) {
+            (SensorMemoryNaiveState::Present, _) => false,
+            (_, SensorMemoryNaiveState::Present) => true,
+            (
+                SensorMemoryNaiveState::AbsentSince(from_duration),
+                SensorMemoryNaiveState::AbsentSince(to_duration),
+            ) => {
+                to_duration < from_duration
+                    && (from_duration > &self.current_room_threshold
+                        || self.left_rooms.contains(from))
+            }
+            _ => false,
+        };
+        is_left && self.is_handover_allowed(from, &to.room, rooms)
+    }
+
+    fn sort_rooms(rooms: &HashMap<Room, SensorMemoryNaiveState>) -> BTreeSet<RoomState> {
         let mut sorted_rooms = BTreeSet::new();
         for (room, sensor_state) in rooms.iter() {
             sorted_rooms.insert(RoomState {
@@ -202,6 +594,12 @@ impl Strategy {
                 state: sensor_state.clone(),
             });
         }
+        sorted_rooms
+    }
+
+    fn calculate_single_current_room(&mut self, rooms: &HashMap<Room, SensorMemoryNaiveState>) {
+        // prepare sorted_rooms list
+        let sorted_rooms = Strategy::sort_rooms(rooms);
         let sorted_rooms: Vec<&RoomState> = Vec::from_iter(sorted_rooms.iter());
         if sorted_rooms.get(1).is_none() {
             //debug!("because only one room is known current_room tracking is disabled");
@@ -223,6 +621,11 @@ impl Strategy {
             if current_room == self.current_room {
                 return;
             }
+            if let (Some(from), Some(to)) = (&self.current_room, &current_room) {
+                if !self.is_handover_allowed(from, to, rooms) {
+                    return;
+                }
+            }
             self.current_room = current_room;
             debug!(
                 "because one room is present and all other rooms are absent, current_room : {:?}",
@@ -274,7 +677,11 @@ impl Strategy {
                     // current_room is still shorter absent
                     return;
                 }
-                if current_room_duration > &self.current_room_threshold {
+                let other_room = &sorted_rooms.get(room_compare_index).unwrap().room;
+                // a room left through a door doesn't need to be absent long
+                let is_left = current_room_duration > &self.current_room_threshold
+                    || self.left_rooms.contains(&current_room);
+                if is_left && self.is_handover_allowed(&current_room, other_room, rooms) {
                     self.current_room = sorted_rooms
                         .get(room_compare_index)
                         .map(|room_state| room_state.room.clone());
@@ -287,6 +694,140 @@ impl Strategy {
         }
     }
 
+    fn update_present_since(&mut self, rooms: &HashMap<Room, SensorMemoryNaiveState>) {
+        for (room, state) in rooms.iter() {
+            if state == &SensorMemoryNaiveState::Present {
+                if !self.present_since.contains_key(room) {
+                    self.present_since.insert(room.clone(), Instant::now());
+                }
+            } else {
+                self.present_since.remove(room);
+            }
+        }
+    }
+
+    /// a handover to a room which is not adjacent to the current room
+    /// needs stronger evidence : a path through rooms which have been visited
+    /// after the current room was left, or the new room must be present long enough.
+    fn is_handover_allowed(
+        &self,
+        from: &Room,
+        to: &Room,
+        rooms: &HashMap<Room, SensorMemoryNaiveState>,
+    ) -> bool {
+        if self.room_graph.is_adjacent(from, to) {
+            return true;
+        }
+        let from_state = rooms.get(from);
+        let visited_after_leaving = |room: &str| match (rooms.get(room), from_state) {
+            (Some(SensorMemoryNaiveState::Present), _) => true,
+            (
+                Some(SensorMemoryNaiveState::AbsentSince(room_duration)),
+                Some(SensorMemoryNaiveState::AbsentSince(from_duration)),
+            ) => room_duration <= from_duration,
+            _ => false,
+        };
+        if self.room_graph.has_path(from, to, visited_after_leaving) {
+            return true;
+        }
+        let handover_delay = self.room_graph.get_handover_delay(to);
+        match self.present_since.get(to) {
+            Some(instant) if instant.elapsed() >= handover_delay => true,
+            _ => {
+                trace!(
+                    "handover {} -> {} rejected, rooms are not adjacent",
+                    from,
+                    to
+                );
+                false
+            }
+        }
+    }
+
+    /// the next instant at which the room or switch states can change
+    /// without any new update. Too early deadlines are fine, the state is
+    /// just recalculated without changes.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let now = Instant::now();
+        let mut deadlines = Vec::new();
+        let mut switch_delays = Vec::new();
+        for switch in self.room_switches.iter() {
+            switch_delays.push(switch.delay);
+            if let Some(dim_delay) = switch.delay.checked_sub(switch.dim_before_off) {
+                switch_delays.push(dim_delay);
+            }
+        }
+        for room_sensors in self.room_sensors.values() {
+            for sensor_memory in room_sensors.values() {
+                if let Some(heartbeat) = sensor_memory.heartbeat {
+                    deadlines.push(sensor_memory.last_seen + heartbeat);
+                    if sensor_memory.ignore_when_stale {
+                        deadlines.push(sensor_memory.last_seen + sensor_memory.delay);
+                    }
+                }
+                let instant = match sensor_memory.state {
+                    SensorMemoryState::AbsentSince(instant) => instant,
+                    SensorMemoryState::Uninitialized => {
+                        deadlines.push(self.started + sensor_memory.delay);
+                        continue;
+                    }
+                    SensorMemoryState::Present => continue,
+                };
+                let absent = instant + sensor_memory.delay;
+                deadlines.push(absent);
+                // current room calculation looks ahead
+                if let Some(tracking_absent) = absent.checked_sub(self.look_ahead) {
+                    deadlines.push(tracking_absent);
+                    deadlines.push(tracking_absent + self.current_room_threshold);
+                }
+                for delay in switch_delays.iter() {
+                    deadlines.push(absent + *delay);
+                }
+            }
+        }
+        for (room, instant) in self.present_since.iter() {
+            deadlines.push(*instant + self.room_graph.get_handover_delay(room));
+        }
+        for switch in self.room_switches.iter() {
+            if let Some(retry_at) = switch.pending.as_ref().and_then(|pending| pending.retry_at) {
+                deadlines.push(retry_at);
+            }
+        }
+        if let (Some(away_config), false) = (&self.away_config, self.away) {
+            let after = Duration::from_secs(away_config.after);
+            for room_sensors in self.room_sensors.values() {
+                for sensor_memory in room_sensors.values() {
+                    if let SensorMemoryState::AbsentSince(instant) = sensor_memory.state {
+                        deadlines.push(instant + sensor_memory.delay + after);
+                    }
+                }
+            }
+            if let Some(door_open) = self.last_door_open {
+                deadlines.push(door_open + Duration::from_secs(away_config.door_timeout));
+            }
+        }
+        if let Some(history) = &self.vacation {
+            let timestamp = now_timestamp();
+            let next_change = history.next_change(timestamp);
+            deadlines.push(now + Duration::from_secs(next_change.saturating_sub(timestamp)));
+        }
+        if self.circadian_enabled {
+            if let Some(circadian) = &self.circadian {
+                match self.circadian_updated {
+                    Some(updated) => {
+                        deadlines.push(updated + Duration::from_secs(circadian.update_interval))
+                    }
+                    // not applied yet, apply it right away
+                    None => return Some(now),
+                }
+            }
+        }
+        deadlines
+            .into_iter()
+            .filter(|deadline| *deadline > now)
+            .min()
+    }
+
     /// trigger switch commands to set switch to expected state
     ///
     /// # Arguments
@@ -294,30 +835,44 @@ impl Strategy {
     /// * `ignore_current_state` : if set to true, all switch commands will be triggered.
     ///    if false, only states that differ current state will trigger commands
     pub fn trigger_commands(&mut self, ignore_current_state: bool) -> Vec<SwitchCommand> {
-        let new_room_states = self.get_room_state(Duration::from_secs(0));
+        let mut new_room_states = self.get_room_state(Duration::from_secs(0));
+        for room in self.simulated_rooms.iter() {
+            if let Some(state) = new_room_states.get_mut(room) {
+                *state = SensorMemoryNaiveState::Present;
+            }
+        }
         Strategy::print_room_update_information(&new_room_states, &self.room_state);
         self.room_state = new_room_states;
 
         // update commands
         let mut commands = Vec::new();
-        for switch in self.room_switches.iter() {
+        for index in 0..self.room_switches.len() {
             use SwitchState::{Off, On};
+            let switch = &self.room_switches[index];
             let mut should_state = None;
-            if self.disabled_switches.contains(&switch.topic) {
+            // should the switch be dimmed, because it will be turned off soon
+            let mut full_brightness = true;
+            let mut dimmed = false;
+            if let Some(state) = self.switch_overrides.get(&switch.topic) {
+                should_state = Some(*state);
+            } else if self.disabled_switches.contains(&switch.topic) {
                 should_state = Some(Off);
             } else if self.enabled_switches.contains(&switch.topic) {
                 should_state = Some(On);
             } else if self.ignored_switches.contains(&switch.topic) {
-                continue;
+                // ignored switches have no expected state
             } else {
+                full_brightness = false;
                 'find_should_state: for room in switch.rooms.iter() {
-                    if Some(room) == self.current_room.as_ref() && self.room_tracking_enabled {
+                    if self.is_current_room(room) && self.room_tracking_enabled {
                         should_state = Some(On);
+                        full_brightness = true;
                         break 'find_should_state;
                     }
                     match &self.room_state.get(room).unwrap() {
                         SensorMemoryNaiveState::Present => {
                             should_state = Some(On);
+                            full_brightness = true;
                             break 'find_should_state;
                         }
                         SensorMemoryNaiveState::AbsentSince(duration) => {
@@ -329,6 +884,15 @@ impl Strategy {
                                     duration.as_secs()
                                 );
                                 should_state = Some(Off);
+                            } else if *duration + switch.dim_before_off > switch.delay {
+                                trace!(
+                                    "{} with delay {}s is - DIMMED - because of AbsentSince({}s)",
+                                    switch.topic,
+                                    switch.delay.as_secs(),
+                                    duration.as_secs()
+                                );
+                                should_state = Some(On);
+                                dimmed = true;
                             } else {
                                 trace!(
                                     "{} with delay {}s is - ON - because of AbsentSince({}s)",
@@ -337,22 +901,62 @@ impl Strategy {
                                     duration.as_secs()
                                 );
                                 should_state = Some(On);
+                                full_brightness = true;
                             }
                         }
                         _ => {}
                     }
                 }
             }
+            // a pending command which is not expected anymore must not be retried
+            let pending_state = switch.pending.as_ref().map(|pending| pending.command.state);
+            if pending_state.is_some() && pending_state != should_state {
+                debug!("cancel pending {:?} of {}", pending_state, switch.topic);
+                self.room_switches[index].pending = None;
+            }
             if should_state.is_none() {
                 continue;
             }
-            if should_state.unwrap() != switch.state || ignore_current_state {
+            let switch = &self.room_switches[index];
+            let (mut brightness, color_temp, color) = self.get_light_settings(switch);
+            if dimmed && !full_brightness {
+                brightness = brightness.min(switch.dim_brightness);
+            }
+            let settings_changed = should_state == Some(On)
+                && switch.state == On
+                && switch.brightness.is_some_and(|current| {
+                    current != brightness || switch.color_temp != color_temp
+                });
+            // the command is already sent, retries are up to retry_commands
+            let already_pending = match &switch.pending {
+                Some(pending) => Some(pending.command.state) == should_state,
+                None => false,
+            };
+            if already_pending && !ignore_current_state && !settings_changed {
+                continue;
+            }
+            if should_state.unwrap() != switch.state || ignore_current_state || settings_changed {
                 trace!("set {} -> {:?}", switch.topic, should_state.unwrap());
-                commands.push(SwitchCommand {
+                let command = SwitchCommand {
                     topic: switch.topic.clone(),
                     state: should_state.unwrap(),
-                    brightness: self.brightness,
-                })
+                    brightness,
+                    color_temp,
+                    color,
+                };
+                if let Some(timeout) = switch.confirm_timeout {
+                    self.room_switches[index].pending = Some(PendingCommand {
+                        command: command.clone(),
+                        retries: 0,
+                        retry_at: Some(Instant::now() + timeout),
+                    });
+                }
+                commands.push(command);
+                self.room_switches[index].brightness = match should_state {
+                    Some(On) => Some(brightness),
+                    _ => None,
+                };
+                self.room_switches[index].color_temp = color_temp;
             }
         }
         commands
@@ -382,74 +986,579 @@ impl Strategy {
         }
     }
 
-    pub fn set_brightness(&mut self, brightness: u8) {
-        self.brightness = brightness;
+    fn is_current_room(&self, room: &Room) -> bool {
+        if self.occupants > 1 {
+            self.current_rooms.contains(room)
+        } else {
+            Some(room) == self.current_room.as_ref()
+        }
     }
 
-    pub fn set_room_tracking_enabled(&mut self, room_tracking_enabled: bool) {
-        self.room_tracking_enabled = room_tracking_enabled;
+    pub fn set_occupants(&mut self, occupants: usize) {
+        self.occupants = occupants.max(1);
+        self.current_rooms = self.current_room.iter().cloned().collect();
     }
 
-    pub fn set_disabled_switches(&mut self, disabled_switches: Vec<String>) {
-        self.disabled_switches = disabled_switches;
+    /// brightness, color temperature and color for a switch.
+    /// switch_settings win over room_settings, which win over the scene settings
+    fn get_light_settings(&self, switch: &SwitchMemory) -> (u8, Option<u16>, Option<Color>) {
+        let mut brightness = self.brightness;
+        let mut color_temp = self.color_temp;
+        let mut color = self.color.as_ref();
+        let room_settings = switch
+            .rooms
+            .iter()
+            .filter_map(|room| self.room_settings.get(room))
+            .next();
+        for settings in room_settings
+            .iter()
+            .chain(self.switch_settings.get(&switch.topic).iter())
+        {
+            if let Some(value) = settings.brightness {
+                brightness = value;
+            }
+            if let Some(value) = settings.color_temp {
+                color_temp = Some(value);
+            }
+            if let Some(value) = settings.color.as_ref() {
+                color = Some(value);
+            }
+        }
+        if !switch.color_support {
+            color = None;
+        }
+        (brightness, color_temp, color.cloned())
     }
 
-    pub fn set_enabled_switches(&mut self, enabled_switches: Vec<String>) {
-        self.enabled_switches = enabled_switches;
+    /// apply all settings of a scene and remove all overlays
+    pub fn set_scene(&mut self, scene: &Scene) {
+        self.base_scene = Some(scene.clone());
+        self.overlays.clear();
+        self.manual_brightness = None;
+        self.manual_room_tracking = None;
+        self.apply_scene(scene);
     }
 
-    pub fn set_ignored_switches(&mut self, ignored_switches: Vec<String>) {
-        self.ignored_switches = ignored_switches;
+    /// activate away when all rooms are absent long enough, or a door was
+    /// opened without any motion afterwards. Presence deactivates away.
+    pub fn update_away(&mut self, now: Instant) {
+        let away_config = match &self.away_config {
+            Some(away_config) => away_config,
+            None => return,
+        };
+        let rooms = self.get_room_state(Duration::from_secs(0));
+        let present = rooms
+            .values()
+            .any(|state| *state == SensorMemoryNaiveState::Present);
+        if self.away {
+            if present {
+                self.set_away(false);
+            }
+            return;
+        }
+        if present {
+            return;
+        }
+        let after = Duration::from_secs(away_config.after);
+        let all_absent = !rooms.is_empty()
+            && rooms.values().all(|state| match state {
+                SensorMemoryNaiveState::AbsentSince(duration) => *duration >= after,
+                _ => false,
+            });
+        let left_through_door = match self.last_door_open {
+            Some(door_open) => {
+                let no_motion = match self.last_motion {
+                    Some(motion) => motion < door_open,
+                    None => true,
+                };
+                no_motion && now >= door_open + Duration::from_secs(away_config.door_timeout)
+            }
+            None => false,
+        };
+        if all_absent || left_through_door {
+            self.set_away(true);
+        }
     }
 
-    pub fn set_ignored_sensors(&mut self, ignored_sensors: Vec<String>) {
-        self.ignored_sensors = ignored_sensors;
+    fn set_away(&mut self, away: bool) {
+        self.away = away;
+        self.last_door_open = None;
+        if away {
+            info!("everybody is away");
+            if let Some(scene) = self.away_scene.clone() {
+                self.scene_before_away = self.base_scene.clone();
+                self.set_scene(&scene);
+            }
+            let clear_current_room = match &self.away_config {
+                Some(away_config) => away_config.clear_current_room,
+                None => false,
+            };
+            if clear_current_room {
+                self.current_room = None;
+                self.current_rooms.clear();
+                self.present_since.clear();
+            }
+        } else {
+            info!("somebody is back home");
+            if let Some(scene) = self.scene_before_away.take() {
+                self.set_scene(&scene);
+            }
+        }
+        self.update_circadian();
     }
 
-    /// the current state of the room.
-    /// sensor delays are taken into account
-    ///
-    /// # Arguments
-    ///
-    /// * `look_ahead` - look ahead in the future
-    ///
-    fn get_room_state(&self, look_ahead: Duration) -> HashMap<String, SensorMemoryNaiveState> {
-        let mut rooms = HashMap::new();
+    /// recalculate everything which changes over time, then send the commands.
+    /// Runs after every message, so a passed deadline (e.g. circadian) can't get lost.
+    /// The current room is only calculated with `track_rooms`,
+    /// `force_commands` sends the commands even if the switch is already in that state.
+    pub fn on_tick(
+        &mut self,
+        now: Instant,
+        track_rooms: bool,
+        force_commands: bool,
+    ) -> Vec<SwitchCommand> {
+        self.take_over_uninitialized_sensors();
+        self.update_stale_sensors();
+        if track_rooms {
+            self.calculate_current_room();
+        }
+        self.update_circadian();
+        self.update_away(now);
+        self.update_vacation(now_timestamp());
+        let mut commands = self.retry_commands(now);
+        commands.append(&mut self.trigger_commands(force_commands));
+        commands
+    }
 
-        for (room, room_sensors) in self.room_sensors.iter() {
-            let mut current_room_state: SensorMemoryNaiveState =
-                SensorMemoryNaiveState::Uninitialized;
+    /// start the presence simulation with the recorded history, None stops it
+    pub fn set_vacation(&mut self, history: Option<History>) {
+        if history.is_some() {
+            info!("vacation started");
+        } else {
+            info!("vacation stopped");
+        }
+        self.vacation = history;
+        self.update_vacation(now_timestamp());
+    }
 
-            'room_state: for (topic, sensor_memory) in room_sensors.iter() {
-                if self.ignored_sensors.contains(topic) {
-                    continue;
-                }
-                match (
-                    &current_room_state,
-                    &sensor_memory.get_naive_state(look_ahead),
-                ) {
-                    (_, SensorMemoryNaiveState::Uninitialized) => {}
+    /// replay the recorded presence while on vacation.
+    /// With away mode configured, presence is only simulated while away.
+    pub fn update_vacation(&mut self, timestamp: u64) {
+        let simulated_rooms = match &self.vacation {
+            Some(history) if self.away || self.away_config.is_none() => {
+                history.get_present_rooms(timestamp)
+            }
+            _ => BTreeSet::new(),
+        };
+        if simulated_rooms != self.simulated_rooms {
+            info!("simulate presence in {:?}", simulated_rooms);
+            self.simulated_rooms = simulated_rooms;
+        }
+    }
 
-                    (_, SensorMemoryNaiveState::Present) => {
-                        current_room_state = SensorMemoryNaiveState::Present;
-                        break 'room_state;
-                    }
+    /// the scene after the active scene in the list,
+    /// the first scene if the active scene is not part of the list
+    pub fn next_scene<'a>(&self, scenes: &'a [Scene]) -> Option<&'a Scene> {
+        let index = scenes
+            .iter()
+            .position(|scene| Some(&scene.name) == self.scene.as_ref())
+            .map(|index| (index + 1) % scenes.len())
+            .unwrap_or(0);
+        scenes.get(index)
+    }
 
-                    (
-                        SensorMemoryNaiveState::AbsentSince(current_duration),
-                        SensorMemoryNaiveState::AbsentSince(new_duration),
-                    ) => {
-                        if current_duration > new_duration {
-                            current_room_state =
-                                SensorMemoryNaiveState::AbsentSince(new_duration.clone());
-                        }
-                    }
+    /// stack an overlay on top of the active scene,
+    /// an overlay with the same name is replaced
+    pub fn add_overlay(&mut self, overlay: Overlay) {
+        // the overlay is newer than manual changes of the same setting
+        if overlay.brightness.is_some() {
+            self.manual_brightness = None;
+        }
+        if overlay.room_tracking_enabled.is_some() {
+            self.manual_room_tracking = None;
+        }
+        self.overlays.retain(|active| active.name != overlay.name);
+        self.overlays.push(overlay);
+        self.apply_overlays();
+    }
 
-                    (
-                        SensorMemoryNaiveState::Uninitialized,
-                        SensorMemoryNaiveState::AbsentSince(duration),
-                    ) => {
-                        current_room_state = SensorMemoryNaiveState::AbsentSince(duration.clone());
+    /// remove an overlay, to revert to the scene without it
+    pub fn remove_overlay(&mut self, name: &str) {
+        self.overlays.retain(|active| active.name != name);
+        self.apply_overlays();
+    }
+
+    /// apply the settings of the scene with all overlays.
+    /// Switch overrides and manual changes are kept.
+    fn apply_overlays(&mut self) {
+        let mut scene = match self.base_scene.clone() {
+            Some(scene) => scene,
+            None => return,
+        };
+        for overlay in self.overlays.iter() {
+            overlay.apply(&mut scene);
+        }
+        let circadian_enabled = self.circadian_enabled;
+        let (brightness, color_temp) = (self.brightness, self.color_temp);
+        self.apply_scene_settings(&scene);
+        if circadian_enabled && self.circadian_enabled {
+            // the circadian curve keeps its settings until its next update
+            self.brightness = brightness;
+            self.color_temp = color_temp;
+        } else if self.circadian_enabled {
+            self.circadian_updated = None;
+        }
+        if let Some(brightness) = self.manual_brightness {
+            self.set_brightness(brightness);
+        }
+        if let Some(room_tracking_enabled) = self.manual_room_tracking {
+            self.set_room_tracking_enabled(room_tracking_enabled);
+        }
+    }
+
+    fn apply_scene(&mut self, scene: &Scene) {
+        self.apply_scene_settings(scene);
+        self.switch_overrides.clear();
+        self.circadian_updated = None;
+    }
+
+    fn apply_scene_settings(&mut self, scene: &Scene) {
+        self.scene = Some(scene.name.clone());
+        self.set_brightness(scene.brightness);
+        self.set_room_tracking_enabled(scene.room_tracking_enabled);
+        self.set_disabled_switches(scene.disabled_switches.clone());
+        self.set_enabled_switches(scene.enabled_switches.clone());
+        self.set_ignored_switches(scene.ignored_switches.clone());
+        self.set_ignored_sensors(scene.ignored_sensors.clone());
+        self.color_temp = scene.color_temp;
+        self.color = scene.color.clone();
+        self.room_settings = scene.room_settings.clone();
+        self.switch_settings = scene.switch_settings.clone();
+        self.circadian_enabled = scene.circadian;
+    }
+
+    /// snapshot of the runtime state, to restore it after a restart
+    pub fn snapshot(&self) -> Snapshot {
+        let mut sensors = HashMap::new();
+        for room_sensors in self.room_sensors.values() {
+            for (topic, sensor_memory) in room_sensors.iter() {
+                let sensor_snapshot = match sensor_memory.state {
+                    SensorMemoryState::Uninitialized => continue,
+                    SensorMemoryState::Present => SensorSnapshot::Present,
+                    SensorMemoryState::AbsentSince(instant) => {
+                        SensorSnapshot::AbsentSince(to_timestamp(instant))
+                    }
+                };
+                sensors.insert(topic.clone(), sensor_snapshot);
+            }
+        }
+        let mut switches = HashMap::new();
+        for switch in self.room_switches.iter() {
+            if switch.state != SwitchState::Unknown {
+                switches.insert(switch.topic.clone(), switch.state);
+            }
+        }
+        Snapshot {
+            timestamp: now_timestamp(),
+            scene: self.scene.clone(),
+            overlays: self.get_overlay_names(),
+            current_room: self.current_room.clone(),
+            current_rooms: self.current_rooms.clone(),
+            occupants: self.occupants,
+            brightness: self.manual_brightness,
+            room_tracking: self.manual_room_tracking,
+            sensors,
+            switches,
+            switch_overrides: self.switch_overrides.clone(),
+            away: self.away,
+            vacation: self.vacation.is_some(),
+            scene_before_away: self
+                .scene_before_away
+                .as_ref()
+                .map(|scene| scene.name.clone()),
+        }
+    }
+
+    /// current status, to show it to the user
+    pub fn status(&self) -> EngineStatus {
+        let rooms = self
+            .get_room_state(Duration::from_secs(0))
+            .into_iter()
+            .map(|(room, state)| (room, state == SensorMemoryNaiveState::Present))
+            .collect();
+        EngineStatus {
+            scene: self.scene.clone(),
+            overlays: self.get_overlay_names(),
+            current_room: self.current_room.clone(),
+            rooms,
+            brightness: self.brightness,
+            room_tracking: self.room_tracking_enabled,
+            switches: self
+                .room_switches
+                .iter()
+                .map(|switch| (switch.topic.clone(), switch.state))
+                .collect(),
+            switch_overrides: self
+                .switch_overrides
+                .iter()
+                .map(|(topic, state)| (topic.clone(), *state))
+                .collect(),
+            stale_sensors: self.stale_sensors.iter().cloned().collect(),
+            unresponsive_switches: self
+                .room_switches
+                .iter()
+                .filter(|switch| switch.is_unresponsive())
+                .map(|switch| switch.topic.clone())
+                .collect(),
+            away: self.away,
+            vacation: self.vacation.is_some(),
+        }
+    }
+
+    fn get_overlay_names(&self) -> Vec<String> {
+        self.overlays
+            .iter()
+            .map(|overlay| overlay.name.clone())
+            .collect()
+    }
+
+    /// restore the runtime state of a snapshot
+    pub fn restore(&mut self, snapshot: &Snapshot, configuration: &Configuration) {
+        let scene = snapshot
+            .scene
+            .as_ref()
+            .and_then(|name| configuration.get_scene(name));
+        if let Some(scene) = scene {
+            self.set_scene(scene);
+        }
+        for name in snapshot.overlays.iter() {
+            if let Some(overlay) = configuration.get_overlay(name) {
+                self.add_overlay(overlay.clone());
+            }
+        }
+        if let Some(brightness) = snapshot.brightness {
+            self.change_brightness(brightness);
+        }
+        if let Some(room_tracking_enabled) = snapshot.room_tracking {
+            self.change_room_tracking(room_tracking_enabled);
+        }
+        self.switch_overrides.clear();
+        for (topic, state) in snapshot.switch_overrides.iter() {
+            if !self
+                .room_switches
+                .iter()
+                .any(|switch| &switch.topic == topic)
+            {
+                warn!("skip override of unknown switch {}", topic);
+                continue;
+            }
+            self.set_switch_override(topic.clone(), Some(*state));
+        }
+        self.away = snapshot.away;
+        self.scene_before_away = snapshot
+            .scene_before_away
+            .as_ref()
+            .and_then(|name| configuration.get_scene(name))
+            .cloned();
+        if let (true, Some(vacation)) = (snapshot.vacation, &configuration.vacation) {
+            let path = Path::new(&vacation.history_file);
+            match History::load_from_file(path, vacation.days, vacation.jitter, now_timestamp()) {
+                Ok(history) => self.set_vacation(Some(history)),
+                Err(e) => warn!("couldn't load history {} : {}", vacation.history_file, e),
+            }
+        }
+        self.occupants = snapshot.occupants.max(1);
+        // e.g. after switching to the presence strategy
+        if self.room_tracking_available {
+            self.current_room = snapshot.current_room.clone();
+            self.current_rooms = snapshot.current_rooms.clone();
+        }
+        for room_sensors in self.room_sensors.values_mut() {
+            for (topic, sensor_memory) in room_sensors.iter_mut() {
+                let state = match snapshot.sensors.get(topic) {
+                    Some(SensorSnapshot::Present) => SensorMemoryState::Present,
+                    Some(SensorSnapshot::AbsentSince(timestamp)) => match to_instant(*timestamp) {
+                        Some(instant) => SensorMemoryState::AbsentSince(instant),
+                        None => continue,
+                    },
+                    None => continue,
+                };
+                sensor_memory.state = state;
+            }
+        }
+        for switch in self.room_switches.iter_mut() {
+            if let Some(state) = snapshot.switches.get(&switch.topic) {
+                switch.state = *state;
+            }
+        }
+        info!(
+            "restored scene: {:?}, current room: {:?}",
+            self.scene, self.current_room
+        );
+    }
+
+    /// apply the circadian curve for the current time of the day,
+    /// if the scene follows it. Updates are rate limited by the update interval.
+    pub fn update_circadian(&mut self) {
+        let now = chrono::Local::now();
+        self.update_circadian_at(Instant::now(), now.hour() * 60 + now.minute());
+    }
+
+    fn update_circadian_at(&mut self, instant: Instant, minute_of_day: u32) {
+        if !self.circadian_enabled {
+            return;
+        }
+        let circadian = match &self.circadian {
+            Some(circadian) => circadian,
+            None => return,
+        };
+        let update_interval = Duration::from_secs(circadian.update_interval);
+        if let Some(updated) = self.circadian_updated {
+            if instant.duration_since(updated) < update_interval {
+                return;
+            }
+        }
+        if let Some((brightness, color_temp)) = circadian.get_settings(minute_of_day) {
+            debug!(
+                "circadian brightness: {}, color temperature: {:?}",
+                brightness, color_temp
+            );
+            // a manual brightness wins until the next scene change
+            if self.manual_brightness.is_none() {
+                self.brightness = brightness;
+            }
+            self.color_temp = color_temp;
+        }
+        self.circadian_updated = Some(instant);
+    }
+
+    /// change the brightness manually, until the next scene change
+    pub fn change_brightness(&mut self, brightness: u8) {
+        self.manual_brightness = Some(brightness);
+        self.set_brightness(brightness);
+    }
+
+    /// change the scene on request, a scene chosen while away is kept
+    pub fn change_scene(&mut self, scene: &Scene) {
+        self.scene_before_away = None;
+        self.set_scene(scene);
+    }
+
+    /// change room tracking manually, until the next scene change
+    pub fn change_room_tracking(&mut self, room_tracking_enabled: bool) {
+        self.manual_room_tracking = Some(room_tracking_enabled);
+        self.set_room_tracking_enabled(room_tracking_enabled);
+    }
+
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    /// override the state of a switch, None removes the override
+    pub fn set_switch_override(&mut self, topic: String, state: Option<SwitchState>) {
+        match state {
+            Some(SwitchState::Unknown) => {
+                warn!("ignore override of {} with an unknown state", topic);
+            }
+            Some(state) => {
+                self.switch_overrides.insert(topic, state);
+            }
+            None => {
+                self.switch_overrides.remove(&topic);
+            }
+        }
+    }
+
+    pub fn set_room_tracking_enabled(&mut self, room_tracking_enabled: bool) {
+        self.room_tracking_enabled = room_tracking_enabled && self.room_tracking_available;
+    }
+
+    /// if not available, room tracking stays disabled, even if scenes enable it
+    pub fn set_room_tracking_available(&mut self, room_tracking_available: bool) {
+        self.room_tracking_available = room_tracking_available;
+        self.room_tracking_enabled = self.room_tracking_enabled && room_tracking_available;
+    }
+
+    pub fn set_disabled_switches(&mut self, disabled_switches: Vec<String>) {
+        self.disabled_switches = disabled_switches;
+    }
+
+    pub fn set_enabled_switches(&mut self, enabled_switches: Vec<String>) {
+        self.enabled_switches = enabled_switches;
+    }
+
+    pub fn set_ignored_switches(&mut self, ignored_switches: Vec<String>) {
+        self.ignored_switches = ignored_switches;
+    }
+
+    pub fn set_ignored_sensors(&mut self, ignored_sensors: Vec<String>) {
+        self.ignored_sensors = ignored_sensors;
+    }
+
+    /// the current state of the room.
+    /// sensor delays are taken into account
+    ///
+    /// # Arguments
+    ///
+    /// * `look_ahead` - look ahead in the future
+    ///
+    fn get_room_state(&self, look_ahead: Duration) -> HashMap<String, SensorMemoryNaiveState> {
+        self.get_filtered_room_state(look_ahead, true)
+    }
+
+    /// the room state used to determine the current room.
+    /// transit sensors (e.g. doors) are not taken into account
+    fn get_tracking_room_state(
+        &self,
+        look_ahead: Duration,
+    ) -> HashMap<String, SensorMemoryNaiveState> {
+        self.get_filtered_room_state(look_ahead, false)
+    }
+
+    fn get_filtered_room_state(
+        &self,
+        look_ahead: Duration,
+        include_transit_sensors: bool,
+    ) -> HashMap<String, SensorMemoryNaiveState> {
+        let mut rooms = HashMap::new();
+
+        for (room, room_sensors) in self.room_sensors.iter() {
+            let mut current_room_state: SensorMemoryNaiveState =
+                SensorMemoryNaiveState::Uninitialized;
+
+            'room_state: for (topic, sensor_memory) in room_sensors.iter() {
+                if self.ignored_sensors.contains(topic) {
+                    continue;
+                }
+                if !include_transit_sensors && sensor_memory.kind.is_transit() {
+                    continue;
+                }
+                match (
+                    &current_room_state,
+                    &sensor_memory.get_naive_state(look_ahead),
+                ) {
+                    (_, SensorMemoryNaiveState::Uninitialized) => {}
+
+                    (_, SensorMemoryNaiveState::Present) => {
+                        current_room_state = SensorMemoryNaiveState::Present;
+                        break 'room_state;
+                    }
+
+                    (
+                        SensorMemoryNaiveState::AbsentSince(current_duration),
+                        SensorMemoryNaiveState::AbsentSince(new_duration),
+                    ) => {
+                        if current_duration > new_duration {
+                            current_room_state =
+                                SensorMemoryNaiveState::AbsentSince(new_duration.clone());
+                        }
+                    }
+
+                    (
+                        SensorMemoryNaiveState::Uninitialized,
+                        SensorMemoryNaiveState::AbsentSince(duration),
+                    ) => {
+                        current_room_state = SensorMemoryNaiveState::AbsentSince(duration.clone());
                     }
 
                     (
@@ -466,11 +1575,89 @@ impl Strategy {
     }
 }
 
+impl DecisionEngine for Strategy {
+    fn handle(&mut self, message: UpdateMessage) -> Vec<SwitchCommand> {
+        let mut track_rooms = false;
+        let mut force_commands = false;
+        match message {
+            UpdateMessage::Ping => {
+                track_rooms = true;
+            }
+            UpdateMessage::SwitchChange(instant, switch_content) => {
+                self.update_switch(instant, switch_content);
+            }
+            UpdateMessage::SensorChange(instant, sensor_content) => {
+                self.update_sensor(instant, sensor_content);
+                track_rooms = true;
+            }
+            UpdateMessage::SensorHeartbeat(instant, topic) => {
+                self.update_sensor_heartbeat(instant, &topic);
+            }
+            UpdateMessage::OccupantsChange(occupants) => {
+                self.set_occupants(occupants);
+                track_rooms = true;
+            }
+            UpdateMessage::BrightnessChange(brightness) => {
+                self.change_brightness(brightness);
+            }
+            UpdateMessage::RoomTrackingChange(room_tracking_enabled) => {
+                self.change_room_tracking(room_tracking_enabled);
+            }
+            UpdateMessage::RoomTrackingToggle => {
+                self.change_room_tracking(!self.room_tracking_enabled);
+            }
+            UpdateMessage::SwitchOverride(topic, state) => {
+                self.set_switch_override(topic, state);
+            }
+            UpdateMessage::VacationChange(history) => {
+                self.set_vacation(history);
+            }
+            UpdateMessage::SceneChange(scene) => {
+                self.change_scene(&scene);
+                force_commands = true;
+            }
+            UpdateMessage::SceneCycle(scenes) => {
+                if let Some(scene) = self.next_scene(&scenes) {
+                    info!("change scene to {}", scene.name);
+                    self.change_scene(scene);
+                }
+                force_commands = true;
+            }
+            UpdateMessage::AddOverlay(overlay) => {
+                self.add_overlay(overlay);
+                force_commands = true;
+            }
+            UpdateMessage::RemoveOverlay(name) => {
+                self.remove_overlay(&name);
+                force_commands = true;
+            }
+        };
+        self.on_tick(Instant::now(), track_rooms, force_commands)
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        Strategy::next_deadline(self)
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Strategy::snapshot(self)
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot, configuration: &Configuration) {
+        Strategy::restore(self, snapshot, configuration)
+    }
+
+    fn status(&self) -> EngineStatus {
+        Strategy::status(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::configuration::{Credentials, Scene, Sensor};
-    use crate::dummy_configuration::create_light_switch;
+    use crate::configuration::{Keyframe, Room, Sensor, SensorKind};
+    use crate::dummy_configuration::{create_light_switch, hardcoded_config};
+    use crate::history::HistoryEntry;
     use std::thread;
     use std::time::Duration;
 
@@ -491,12 +1678,20 @@ mod tests {
     }
 
     fn create_sensor(topic: &str, rooms: String, delay: u64) -> Sensor {
+        create_sensor_with_kind(topic, rooms, delay, SensorKind::Motion)
+    }
+
+    fn create_sensor_with_kind(topic: &str, rooms: String, delay: u64, kind: SensorKind) -> Sensor {
         Sensor {
             topic: topic.to_string(),
             key: "occupancy".to_string(),
             invert_state: false,
             delay,
             room: rooms,
+            kind,
+            heartbeat: None,
+            ignore_when_stale: false,
+            qos: 0,
         }
     }
 
@@ -513,17 +1708,13 @@ mod tests {
 
     fn create_test_setup_with_scene(scenes: Vec<Scene>) -> Strategy {
         let configuration = Configuration {
-            credentials: Credentials {
-                host: "".to_string(),
-                user: "".to_string(),
-                password: "".to_string(),
-            },
             scenes,
             sensors: vec![
                 create_sensor("motion1", "room1".to_string(), 10),
                 create_sensor("motion2", "room1".to_string(), 10),
             ],
             switches: vec![create_light_switch("light1", vec!["room1".to_string()])],
+            ..hardcoded_config()
         };
         let strategy = Strategy::new(&configuration);
 
@@ -622,11 +1813,16 @@ mod tests {
         let scene = Scene {
             name: "test".to_string(),
             brightness: 255,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
             disabled_switches: vec![],
             enabled_switches: vec![],
             ignored_switches: vec![],
             room_tracking_enabled: false,
             ignored_sensors: vec!["motion1".to_string()],
+            circadian: false,
         };
         let mut strategy = create_test_setup_with_scene(vec![scene]);
         let motion_1_sensor = strategy
@@ -650,11 +1846,16 @@ mod tests {
         let scene = Scene {
             name: "test".to_string(),
             brightness: 255,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
             disabled_switches: vec![],
             enabled_switches: vec![],
             ignored_switches: vec![],
             room_tracking_enabled: false,
             ignored_sensors: vec!["motion1".to_string(), "motion2".to_string()],
+            circadian: false,
         };
         let mut strategy = create_test_setup_with_scene(vec![scene]);
         let mut room1 = strategy.room_sensors.get_mut("room1").unwrap();
@@ -681,11 +1882,16 @@ mod tests {
         let scene = Scene {
             name: "test".to_string(),
             brightness: 255,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
             disabled_switches: vec![],
             enabled_switches: vec![],
             ignored_switches: vec![],
             room_tracking_enabled: false,
             ignored_sensors: vec!["motion1".to_string()],
+            circadian: false,
         };
         let mut strategy = create_test_setup_with_scene(vec![scene]);
         let mut room1 = strategy.room_sensors.get_mut("room1").unwrap();
@@ -726,6 +1932,8 @@ mod tests {
                 topic: "zigbee2mqtt/light1".to_string(),
                 state: SwitchState::On,
                 brightness: 255,
+                color_temp: None,
+                color: None,
             }
         )
     }
@@ -742,6 +1950,8 @@ mod tests {
                 topic: "zigbee2mqtt/light1".to_string(),
                 state: SwitchState::Off,
                 brightness: 255,
+                color_temp: None,
+                color: None,
             }
         )
     }
@@ -760,10 +1970,1110 @@ mod tests {
                 topic: "zigbee2mqtt/light1".to_string(),
                 state: SwitchState::On,
                 brightness: 255,
+                color_temp: None,
+                color: None,
             }
         );
         strategy.set_ignored_switches(vec!["zigbee2mqtt/light1".to_string()]);
         let commands = strategy.trigger_commands(false);
         assert!(commands.is_empty());
     }
+
+    fn create_room_graph_test_setup() -> Strategy {
+        Strategy::new(&create_room_graph_configuration())
+    }
+
+    fn create_room_graph_configuration() -> Configuration {
+        Configuration {
+            scenes: vec![],
+            sensors: vec![
+                create_sensor("motion1", "room1".to_string(), 10),
+                create_sensor("motion2", "room2".to_string(), 10),
+                create_sensor("motion3", "room3".to_string(), 10),
+            ],
+            switches: vec![],
+            rooms: vec![
+                Room {
+                    name: "room1".to_string(),
+                    adjacent_rooms: vec!["room2".to_string()],
+                    handover_delay: 30,
+                },
+                Room {
+                    name: "room2".to_string(),
+                    adjacent_rooms: vec!["room3".to_string()],
+                    handover_delay: 30,
+                },
+                Room {
+                    name: "room3".to_string(),
+                    adjacent_rooms: vec![],
+                    handover_delay: 30,
+                },
+            ],
+            ..hardcoded_config()
+        }
+    }
+
+    fn set_sensor_state(
+        strategy: &mut Strategy,
+        room: &str,
+        topic: &str,
+        state: SensorMemoryState,
+    ) {
+        strategy
+            .room_sensors
+            .get_mut(room)
+            .unwrap()
+            .get_mut(topic)
+            .unwrap()
+            .state = state;
+    }
+
+    #[test]
+    fn test_calculate_current_room_rejects_non_adjacent_room() {
+        let mut strategy = create_room_graph_test_setup();
+        strategy.current_room = Some("room1".to_string());
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::AbsentSince(instant_from_the_past(60)),
+        );
+        set_sensor_state(
+            &mut strategy,
+            "room2",
+            "motion2",
+            SensorMemoryState::AbsentSince(instant_from_the_past(120)),
+        );
+        set_sensor_state(
+            &mut strategy,
+            "room3",
+            "motion3",
+            SensorMemoryState::Present,
+        );
+        strategy.calculate_current_room();
+        assert_eq!(strategy.current_room, Some("room1".to_string()));
+
+        // room3 is present long enough
+        strategy
+            .present_since
+            .insert("room3".to_string(), instant_from_the_past(40));
+        strategy.calculate_current_room();
+        assert_eq!(strategy.current_room, Some("room3".to_string()));
+    }
+
+    #[test]
+    fn test_calculate_current_room_accepts_path_through_visited_room() {
+        let mut strategy = create_room_graph_test_setup();
+        strategy.current_room = Some("room1".to_string());
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::AbsentSince(instant_from_the_past(120)),
+        );
+        // room2 was left after room1
+        set_sensor_state(
+            &mut strategy,
+            "room2",
+            "motion2",
+            SensorMemoryState::AbsentSince(instant_from_the_past(60)),
+        );
+        set_sensor_state(
+            &mut strategy,
+            "room3",
+            "motion3",
+            SensorMemoryState::Present,
+        );
+        strategy.calculate_current_room();
+        assert_eq!(strategy.current_room, Some("room3".to_string()));
+    }
+
+    #[test]
+    fn test_calculate_current_rooms_with_multiple_occupants() {
+        let mut strategy = create_room_graph_test_setup();
+        strategy.set_occupants(2);
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::Present,
+        );
+        set_sensor_state(
+            &mut strategy,
+            "room2",
+            "motion2",
+            SensorMemoryState::AbsentSince(instant_from_the_past(120)),
+        );
+        set_sensor_state(
+            &mut strategy,
+            "room3",
+            "motion3",
+            SensorMemoryState::AbsentSince(instant_from_the_past(60)),
+        );
+        strategy.calculate_current_room();
+        assert_eq!(
+            strategy.current_rooms,
+            vec!["room1".to_string(), "room3".to_string()]
+        );
+        assert!(strategy.is_current_room(&"room3".to_string()));
+        assert!(!strategy.is_current_room(&"room2".to_string()));
+
+        strategy.set_occupants(1);
+        assert!(!strategy.is_current_room(&"room3".to_string()));
+    }
+
+    #[test]
+    fn test_calculate_current_rooms_keeps_rooms_below_the_threshold() {
+        let mut strategy = create_room_graph_test_setup();
+        strategy.set_occupants(2);
+        strategy.current_rooms = vec!["room1".to_string(), "room2".to_string()];
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::Present,
+        );
+        // room2 was just left, room3 is more recent but not long enough
+        set_sensor_state(
+            &mut strategy,
+            "room2",
+            "motion2",
+            SensorMemoryState::AbsentSince(instant_from_the_past(3)),
+        );
+        set_sensor_state(
+            &mut strategy,
+            "room3",
+            "motion3",
+            SensorMemoryState::AbsentSince(instant_from_the_past(1)),
+        );
+        strategy.calculate_current_room();
+        assert_eq!(
+            strategy.current_rooms,
+            vec!["room1".to_string(), "room2".to_string()]
+        );
+
+        set_sensor_state(
+            &mut strategy,
+            "room3",
+            "motion3",
+            SensorMemoryState::Present,
+        );
+        strategy.calculate_current_room();
+        assert_eq!(
+            strategy.current_rooms,
+            vec!["room1".to_string(), "room3".to_string()]
+        );
+    }
+
+    fn create_sensor_kind_test_setup() -> Strategy {
+        let configuration = Configuration {
+            scenes: vec![],
+            sensors: vec![
+                create_sensor_with_kind("bed1", "room1".to_string(), 10, SensorKind::Bed),
+                create_sensor("motion2", "room2".to_string(), 10),
+                create_sensor_with_kind("door3", "room3".to_string(), 10, SensorKind::Door),
+                create_sensor("motion3", "room3".to_string(), 10),
+            ],
+            switches: vec![],
+            ..hardcoded_config()
+        };
+        Strategy::new(&configuration)
+    }
+
+    #[test]
+    fn test_update_sensor_transit() {
+        let mut strategy = create_sensor_kind_test_setup();
+        strategy.update_sensor(
+            Instant::now(),
+            SensorChangeContent {
+                topic: "door3".to_string(),
+                state: SensorState::Present,
+            },
+        );
+        match strategy
+            .room_sensors
+            .get("room3")
+            .unwrap()
+            .get("door3")
+            .unwrap()
+            .state
+        {
+            SensorMemoryState::AbsentSince(_) => {}
+            _ => panic!("transit sensors should never stay present"),
+        }
+        let map = strategy.get_room_state(Duration::from_secs(0));
+        assert_eq!(&SensorMemoryNaiveState::Present, map.get("room3").unwrap());
+        let map = strategy.get_tracking_room_state(Duration::from_secs(0));
+        assert_eq!(
+            &SensorMemoryNaiveState::Uninitialized,
+            map.get("room3").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_calculate_current_room_sticky() {
+        let mut strategy = create_sensor_kind_test_setup();
+        set_sensor_state(&mut strategy, "room1", "bed1", SensorMemoryState::Present);
+        set_sensor_state(
+            &mut strategy,
+            "room2",
+            "motion2",
+            SensorMemoryState::Present,
+        );
+        strategy.calculate_current_room();
+        assert_eq!(strategy.current_room, Some("room1".to_string()));
+
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "bed1",
+            SensorMemoryState::AbsentSince(instant_from_the_past(60)),
+        );
+        strategy.calculate_current_room();
+        assert_eq!(strategy.current_room, Some("room2".to_string()));
+    }
+
+    #[test]
+    fn test_calculate_current_room_left_through_door() {
+        let mut strategy = create_sensor_kind_test_setup();
+        strategy.current_room = Some("room3".to_string());
+        // both rooms are absent for less than the current room threshold
+        set_sensor_state(
+            &mut strategy,
+            "room3",
+            "motion3",
+            SensorMemoryState::AbsentSince(instant_from_the_past(4)),
+        );
+        set_sensor_state(
+            &mut strategy,
+            "room2",
+            "motion2",
+            SensorMemoryState::AbsentSince(instant_from_the_past(2)),
+        );
+        strategy.calculate_current_room();
+        assert_eq!(strategy.current_room, Some("room3".to_string()));
+
+        // motion after the door was opened, room3 was not left
+        for topic in ["door3", "motion3"].iter() {
+            strategy.update_sensor(
+                Instant::now(),
+                SensorChangeContent {
+                    topic: topic.to_string(),
+                    state: SensorState::Present,
+                },
+            );
+        }
+        assert!(strategy.left_rooms.is_empty());
+        set_sensor_state(
+            &mut strategy,
+            "room3",
+            "motion3",
+            SensorMemoryState::AbsentSince(instant_from_the_past(4)),
+        );
+        strategy.calculate_current_room();
+        assert_eq!(strategy.current_room, Some("room3".to_string()));
+
+        strategy.update_sensor(
+            Instant::now(),
+            SensorChangeContent {
+                topic: "door3".to_string(),
+                state: SensorState::Present,
+            },
+        );
+        strategy.calculate_current_room();
+        assert_eq!(strategy.current_room, Some("room2".to_string()));
+    }
+
+    #[test]
+    fn test_stale_sticky_sensor_does_not_pin() {
+        let mut strategy = create_sensor_kind_test_setup();
+        set_sensor_state(&mut strategy, "room1", "bed1", SensorMemoryState::Present);
+        {
+            let bed = strategy
+                .room_sensors
+                .get_mut("room1")
+                .unwrap()
+                .get_mut("bed1")
+                .unwrap();
+            bed.heartbeat = Some(Duration::from_secs(60));
+            bed.ignore_when_stale = true;
+            bed.last_seen = instant_from_the_past(120);
+        }
+        assert!(strategy.get_pinned_rooms().is_empty());
+    }
+
+    #[test]
+    fn test_trigger_command_light_settings() {
+        let mut room_settings = HashMap::new();
+        room_settings.insert(
+            "room1".to_string(),
+            LightSettings {
+                brightness: Some(180),
+                color_temp: Some(370),
+                color: None,
+            },
+        );
+        let mut switch_settings = HashMap::new();
+        switch_settings.insert(
+            "zigbee2mqtt/light1".to_string(),
+            LightSettings {
+                brightness: Some(40),
+                color_temp: None,
+                color: None,
+            },
+        );
+        let scene = Scene {
+            name: "evening".to_string(),
+            brightness: 255,
+            color_temp: Some(250),
+            color: None,
+            room_settings,
+            switch_settings: HashMap::new(),
+            disabled_switches: vec![],
+            enabled_switches: vec!["zigbee2mqtt/light1".to_string()],
+            ignored_switches: vec![],
+            room_tracking_enabled: false,
+            ignored_sensors: vec![],
+            circadian: false,
+        };
+        let mut strategy = create_test_setup_with_scene(vec![scene.clone()]);
+        let commands = strategy.trigger_commands(false);
+        assert_eq!(commands.first().unwrap().brightness, 180);
+        assert_eq!(commands.first().unwrap().color_temp, Some(370));
+
+        strategy.set_scene(&Scene {
+            switch_settings,
+            ..scene
+        });
+        let commands = strategy.trigger_commands(false);
+        assert_eq!(commands.first().unwrap().brightness, 40);
+        assert_eq!(commands.first().unwrap().color_temp, Some(370));
+    }
+
+    #[test]
+    fn test_trigger_command_color_support() {
+        let scene = Scene {
+            name: "night".to_string(),
+            brightness: 25,
+            color_temp: None,
+            color: Some(Color::Hex("#ff0000".to_string())),
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
+            disabled_switches: vec![],
+            enabled_switches: vec!["zigbee2mqtt/light1".to_string()],
+            ignored_switches: vec![],
+            room_tracking_enabled: false,
+            ignored_sensors: vec![],
+            circadian: false,
+        };
+        let mut strategy = create_test_setup_with_scene(vec![scene]);
+        let commands = strategy.trigger_commands(false);
+        assert_eq!(commands.first().unwrap().color, None);
+
+        strategy.room_switches.get_mut(0).unwrap().color_support = true;
+        let commands = strategy.trigger_commands(false);
+        assert_eq!(
+            commands.first().unwrap().color,
+            Some(Color::Hex("#ff0000".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_trigger_command_dim_before_off() {
+        let mut strategy = create_test_setup();
+        {
+            let switch = strategy.room_switches.get_mut(0).unwrap();
+            switch.delay = Duration::from_secs(60);
+            switch.dim_before_off = Duration::from_secs(30);
+            switch.dim_brightness = 20;
+            switch.state = SwitchState::On;
+            switch.brightness = Some(255);
+        }
+        // 50s absent, sensor delay included
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::AbsentSince(instant_from_the_past(60)),
+        );
+        let commands = strategy.trigger_commands(false);
+        assert_eq!(
+            commands.first().unwrap(),
+            &SwitchCommand {
+                topic: "zigbee2mqtt/light1".to_string(),
+                state: SwitchState::On,
+                brightness: 20,
+                color_temp: None,
+                color: None,
+            }
+        );
+        assert!(strategy.trigger_commands(false).is_empty());
+
+        // presence restores full brightness
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::Present,
+        );
+        let commands = strategy.trigger_commands(false);
+        assert_eq!(commands.first().unwrap().brightness, 255);
+    }
+
+    #[test]
+    fn test_take_over_uninitialized_sensors() {
+        let mut strategy = create_room_graph_test_setup();
+        strategy.take_over_uninitialized_sensors();
+        assert_eq!(
+            strategy.room_sensors["room1"]["motion1"].state,
+            SensorMemoryState::Uninitialized
+        );
+        assert_eq!(
+            strategy.next_deadline(),
+            Some(strategy.started + Duration::from_secs(10))
+        );
+
+        // every sensor is taken over after its own delay
+        strategy.started = instant_from_the_past(10);
+        strategy
+            .room_sensors
+            .get_mut("room2")
+            .unwrap()
+            .get_mut("motion2")
+            .unwrap()
+            .delay = Duration::from_secs(600);
+        strategy.take_over_uninitialized_sensors();
+        assert_eq!(
+            strategy.room_sensors["room1"]["motion1"].state,
+            SensorMemoryState::AbsentSince(strategy.started)
+        );
+        assert_eq!(
+            strategy.room_sensors["room2"]["motion2"].state,
+            SensorMemoryState::Uninitialized
+        );
+    }
+
+    #[test]
+    fn test_startup_policy_assume_absent() {
+        let mut configuration = create_room_graph_configuration();
+        configuration.startup_policy = StartupPolicy::AssumeAbsent;
+        let strategy = Strategy::new(&configuration);
+        let rooms = strategy.get_room_state(Duration::from_secs(0));
+        match rooms.get("room1").unwrap() {
+            SensorMemoryNaiveState::AbsentSince(duration) => {
+                assert!(duration < &Duration::from_secs(1));
+            }
+            _ => panic!("room1 should be absent"),
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        let mut strategy = create_room_graph_test_setup();
+        set_sensor_state(
+            &mut strategy,
+            "room2",
+            "motion2",
+            SensorMemoryState::Present,
+        );
+        strategy.handle(UpdateMessage::Ping);
+        strategy.handle(UpdateMessage::BrightnessChange(100));
+        strategy.handle(UpdateMessage::RoomTrackingChange(false));
+        let status = strategy.status();
+        assert_eq!(status.current_room, Some("room2".to_string()));
+        assert_eq!(status.rooms.get("room1"), Some(&false));
+        assert_eq!(status.rooms.get("room2"), Some(&true));
+        assert_eq!(status.brightness, 100);
+        assert!(!status.room_tracking);
+    }
+
+    #[test]
+    fn test_switch_override() {
+        let mut strategy = create_test_setup();
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::Present,
+        );
+        let commands = strategy.handle(UpdateMessage::SwitchOverride(
+            "zigbee2mqtt/light1".to_string(),
+            Some(SwitchState::Off),
+        ));
+        assert_eq!(commands.first().unwrap().state, SwitchState::Off);
+        assert_eq!(
+            strategy.status().switch_overrides.get("zigbee2mqtt/light1"),
+            Some(&SwitchState::Off)
+        );
+
+        let commands = strategy.handle(UpdateMessage::SwitchOverride(
+            "zigbee2mqtt/light1".to_string(),
+            None,
+        ));
+        assert_eq!(commands.first().unwrap().state, SwitchState::On);
+
+        // an unknown state can't be sent to the switch
+        strategy.handle(UpdateMessage::SwitchOverride(
+            "zigbee2mqtt/light1".to_string(),
+            Some(SwitchState::Unknown),
+        ));
+        assert!(strategy.status().switch_overrides.is_empty());
+    }
+
+    #[test]
+    fn test_retry_commands() {
+        let mut strategy = create_test_setup();
+        strategy.room_switches[0].confirm_timeout = Some(Duration::from_secs(5));
+        strategy.room_switches[0].max_retries = 1;
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::Present,
+        );
+        let now = Instant::now();
+        assert_eq!(strategy.trigger_commands(false).len(), 1);
+        // wait for the confirmation, instead of sending the command again
+        assert!(strategy.trigger_commands(false).is_empty());
+        assert!(strategy.retry_commands(now).is_empty());
+
+        let commands = strategy.retry_commands(now + Duration::from_secs(6));
+        assert_eq!(commands.first().unwrap().state, SwitchState::On);
+        // backoff
+        assert!(strategy
+            .retry_commands(now + Duration::from_secs(12))
+            .is_empty());
+
+        assert!(strategy
+            .retry_commands(now + Duration::from_secs(30))
+            .is_empty());
+        assert_eq!(
+            strategy.status().unresponsive_switches,
+            vec!["zigbee2mqtt/light1".to_string()]
+        );
+
+        strategy.update_switch(
+            now,
+            SwitchChangeContent {
+                topic: "zigbee2mqtt/light1".to_string(),
+                state: SwitchState::On,
+            },
+        );
+        assert!(strategy.room_switches[0].pending.is_none());
+        assert!(strategy.status().unresponsive_switches.is_empty());
+    }
+
+    #[test]
+    fn test_retry_backoff_is_capped() {
+        let mut strategy = create_test_setup();
+        strategy.room_switches[0].confirm_timeout = Some(Duration::from_secs(1));
+        strategy.room_switches[0].max_retries = 40;
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::Present,
+        );
+        assert_eq!(strategy.trigger_commands(false).len(), 1);
+        let now = Instant::now();
+        strategy.room_switches[0].pending.as_mut().unwrap().retries = 35;
+        strategy.room_switches[0].pending.as_mut().unwrap().retry_at = Some(now);
+        assert_eq!(strategy.retry_commands(now).len(), 1);
+        assert_eq!(
+            strategy.room_switches[0].pending.as_ref().unwrap().retry_at,
+            Some(now + Duration::from_secs(1024))
+        );
+    }
+
+    #[test]
+    fn test_cancel_pending_command() {
+        let mut strategy = create_test_setup();
+        strategy.room_switches[0].confirm_timeout = Some(Duration::from_secs(5));
+        strategy.room_switches[0].state = SwitchState::Off;
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::Present,
+        );
+        let now = Instant::now();
+        assert_eq!(strategy.trigger_commands(false).len(), 1);
+        assert!(strategy.room_switches[0].pending.is_some());
+
+        // the command got lost and the room is absent again, the switch is already off
+        for sensor in ["motion1", "motion2"] {
+            set_sensor_state(
+                &mut strategy,
+                "room1",
+                sensor,
+                SensorMemoryState::AbsentSince(instant_from_the_past(100)),
+            );
+        }
+        assert!(strategy.trigger_commands(false).is_empty());
+        assert!(strategy.room_switches[0].pending.is_none());
+        assert!(strategy
+            .retry_commands(now + Duration::from_secs(6))
+            .is_empty());
+    }
+
+    fn create_away_test_setup() -> Strategy {
+        let scene = |name: &str, brightness: u8| Scene {
+            name: name.to_string(),
+            brightness,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
+            disabled_switches: vec![],
+            enabled_switches: vec![],
+            ignored_switches: vec![],
+            room_tracking_enabled: true,
+            ignored_sensors: vec![],
+            circadian: false,
+        };
+        let mut configuration = create_room_graph_configuration();
+        configuration.scenes = vec![scene("day", 255), scene("away", 10)];
+        configuration.away = Some(Away {
+            after: 60,
+            scene: Some("away".to_string()),
+            topic: Away::default_topic(),
+            clear_current_room: true,
+            doors: vec!["motion3".to_string()],
+            door_timeout: 300,
+        });
+        let mut strategy = Strategy::new(&configuration);
+        for (room, sensor) in [
+            ("room1", "motion1"),
+            ("room2", "motion2"),
+            ("room3", "motion3"),
+        ] {
+            set_sensor_state(
+                &mut strategy,
+                room,
+                sensor,
+                SensorMemoryState::AbsentSince(instant_from_the_past(20)),
+            );
+        }
+        strategy
+    }
+
+    #[test]
+    fn test_away_all_rooms_absent() {
+        let mut strategy = create_away_test_setup();
+        strategy.current_room = Some("room1".to_string());
+        strategy.update_away(Instant::now());
+        assert!(!strategy.away);
+
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::AbsentSince(instant_from_the_past(100)),
+        );
+        set_sensor_state(
+            &mut strategy,
+            "room2",
+            "motion2",
+            SensorMemoryState::AbsentSince(instant_from_the_past(100)),
+        );
+        set_sensor_state(
+            &mut strategy,
+            "room3",
+            "motion3",
+            SensorMemoryState::AbsentSince(instant_from_the_past(100)),
+        );
+        strategy.update_away(Instant::now());
+        assert!(strategy.status().away);
+        assert_eq!(strategy.scene, Some("away".to_string()));
+        assert_eq!(strategy.current_room, None);
+
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::Present,
+        );
+        strategy.update_away(Instant::now());
+        assert!(!strategy.away);
+        assert_eq!(strategy.scene, Some("day".to_string()));
+        assert_eq!(strategy.brightness, 255);
+    }
+
+    #[test]
+    fn test_scene_cycle_while_away_is_kept() {
+        let mut strategy = create_away_test_setup();
+        for (room, sensor) in [
+            ("room1", "motion1"),
+            ("room2", "motion2"),
+            ("room3", "motion3"),
+        ] {
+            set_sensor_state(
+                &mut strategy,
+                room,
+                sensor,
+                SensorMemoryState::AbsentSince(instant_from_the_past(100)),
+            );
+        }
+        strategy.update_away(Instant::now());
+        assert!(strategy.away);
+
+        let mut evening = strategy.away_scene.clone().unwrap();
+        evening.name = "evening".to_string();
+        strategy.handle(UpdateMessage::SceneCycle(vec![evening]));
+        assert_eq!(strategy.scene, Some("evening".to_string()));
+
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::Present,
+        );
+        strategy.update_away(Instant::now());
+        assert!(!strategy.away);
+        assert_eq!(strategy.scene, Some("evening".to_string()));
+    }
+
+    #[test]
+    fn test_away_door() {
+        let mut strategy = create_away_test_setup();
+        strategy.last_door_open = Some(instant_from_the_past(400));
+        strategy.last_motion = Some(instant_from_the_past(390));
+        strategy.update_away(Instant::now());
+        assert!(!strategy.away);
+
+        strategy.last_motion = Some(instant_from_the_past(410));
+        strategy.update_away(Instant::now());
+        assert!(strategy.away);
+        assert!(strategy.last_door_open.is_none());
+    }
+
+    #[test]
+    fn test_vacation() {
+        let mut strategy = create_test_setup();
+        for sensor in ["motion1", "motion2"] {
+            set_sensor_state(
+                &mut strategy,
+                "room1",
+                sensor,
+                SensorMemoryState::AbsentSince(instant_from_the_past(100)),
+            );
+        }
+        strategy.room_switches[0].state = SwitchState::Off;
+        assert!(strategy.handle(UpdateMessage::Ping).is_empty());
+
+        // yesterday, room1 was present since an hour
+        let history = History::new(
+            vec![HistoryEntry {
+                timestamp: now_timestamp() - 25 * 60 * 60,
+                room: "room1".to_string(),
+                present: true,
+            }],
+            1,
+            0,
+        );
+        let commands = strategy.handle(UpdateMessage::VacationChange(Some(history)));
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].state, SwitchState::On);
+        assert!(strategy.status().vacation);
+        strategy.room_switches[0].state = SwitchState::On;
+
+        let commands = strategy.handle(UpdateMessage::VacationChange(None));
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].state, SwitchState::Off);
+        assert!(!strategy.status().vacation);
+    }
+
+    #[test]
+    fn test_stale_sensors() {
+        let mut strategy = create_test_setup();
+        {
+            let sensor_memory = strategy
+                .room_sensors
+                .get_mut("room1")
+                .unwrap()
+                .get_mut("motion1")
+                .unwrap();
+            sensor_memory.heartbeat = Some(Duration::from_secs(60));
+            sensor_memory.last_seen = instant_from_the_past(120);
+        }
+        strategy.handle(UpdateMessage::Ping);
+        assert_eq!(strategy.status().stale_sensors, vec!["motion1".to_string()]);
+
+        strategy.handle(UpdateMessage::SensorHeartbeat(
+            Instant::now(),
+            "motion1".to_string(),
+        ));
+        assert!(strategy.status().stale_sensors.is_empty());
+    }
+
+    #[test]
+    fn test_next_deadline() {
+        let mut strategy = create_test_setup();
+        // uninitialized sensors are already taken over
+        strategy.started = instant_from_the_past(60);
+        assert_eq!(strategy.next_deadline(), None);
+
+        // sensor delay is 10s, current room threshold is 5s, switch delay is 0s
+        let instant = instant_from_the_past(6);
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::AbsentSince(instant),
+        );
+        assert_eq!(
+            strategy.next_deadline(),
+            Some(instant + Duration::from_secs(10))
+        );
+
+        // deadlines in the past are ignored
+        set_sensor_state(
+            &mut strategy,
+            "room1",
+            "motion1",
+            SensorMemoryState::AbsentSince(instant_from_the_past(60)),
+        );
+        assert_eq!(strategy.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let scene = Scene {
+            name: "night".to_string(),
+            brightness: 25,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
+            disabled_switches: vec![],
+            enabled_switches: vec![],
+            ignored_switches: vec![],
+            room_tracking_enabled: true,
+            ignored_sensors: vec![],
+            circadian: false,
+        };
+        let mut configuration = create_room_graph_configuration();
+        configuration.scenes = vec![scene];
+        configuration.switches = vec![create_light_switch("light2", vec!["room2".to_string()])];
+        let mut strategy = Strategy::new(&configuration);
+        strategy.current_room = Some("room2".to_string());
+        set_sensor_state(
+            &mut strategy,
+            "room2",
+            "motion2",
+            SensorMemoryState::AbsentSince(instant_from_the_past(30)),
+        );
+        strategy.room_switches.get_mut(0).unwrap().state = SwitchState::On;
+        let snapshot = strategy.snapshot();
+        assert_eq!(snapshot.scene, Some("night".to_string()));
+
+        let mut restored = Strategy::new(&configuration);
+        restored.restore(&snapshot, &configuration);
+        assert_eq!(restored.current_room, Some("room2".to_string()));
+        assert_eq!(restored.brightness, 25);
+        assert_eq!(
+            restored.room_switches.first().unwrap().state,
+            SwitchState::On
+        );
+        match restored.room_sensors["room2"]["motion2"].state {
+            SensorMemoryState::AbsentSince(instant) => {
+                assert!(instant.elapsed() >= Duration::from_secs(29));
+                assert!(instant.elapsed() <= Duration::from_secs(31));
+            }
+            _ => panic!("motion2 should be absent"),
+        }
+        assert_eq!(
+            restored.room_sensors["room1"]["motion1"].state,
+            SensorMemoryState::Uninitialized
+        );
+
+        // overrides of removed switches and unknown states are dropped
+        let mut snapshot = snapshot;
+        snapshot
+            .switch_overrides
+            .insert("zigbee2mqtt/light2".to_string(), SwitchState::Off);
+        snapshot
+            .switch_overrides
+            .insert("zigbee2mqtt/removed".to_string(), SwitchState::On);
+        restored.restore(&snapshot, &configuration);
+        assert_eq!(restored.switch_overrides.len(), 1);
+        assert_eq!(
+            restored.switch_overrides["zigbee2mqtt/light2"],
+            SwitchState::Off
+        );
+        snapshot
+            .switch_overrides
+            .insert("zigbee2mqtt/light2".to_string(), SwitchState::Unknown);
+        restored.restore(&snapshot, &configuration);
+        assert!(restored.switch_overrides.is_empty());
+    }
+
+    #[test]
+    fn test_scene_cycle() {
+        let scene = |name: &str, brightness: u8| Scene {
+            name: name.to_string(),
+            brightness,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
+            disabled_switches: vec![],
+            enabled_switches: vec![],
+            ignored_switches: vec![],
+            room_tracking_enabled: true,
+            ignored_sensors: vec![],
+            circadian: false,
+        };
+        let scenes = vec![scene("day", 255), scene("evening", 100), scene("night", 25)];
+        let mut strategy = create_test_setup_with_scene(scenes.clone());
+        let cycle = vec![scenes[1].clone(), scenes[2].clone()];
+        // day is not part of the cycle, so it starts with the first scene
+        strategy.handle(UpdateMessage::SceneCycle(cycle.clone()));
+        assert_eq!(strategy.scene, Some("evening".to_string()));
+        strategy.handle(UpdateMessage::SceneCycle(cycle.clone()));
+        assert_eq!(strategy.scene, Some("night".to_string()));
+        strategy.handle(UpdateMessage::SceneCycle(cycle));
+        assert_eq!(strategy.scene, Some("evening".to_string()));
+        assert_eq!(strategy.brightness, 100);
+
+        strategy.handle(UpdateMessage::RoomTrackingToggle);
+        assert!(!strategy.room_tracking_enabled);
+        strategy.handle(UpdateMessage::RoomTrackingToggle);
+        assert!(strategy.room_tracking_enabled);
+    }
+
+    #[test]
+    fn test_overlays() {
+        let scene = Scene {
+            name: "evening".to_string(),
+            brightness: 200,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
+            disabled_switches: vec![],
+            enabled_switches: vec![],
+            ignored_switches: vec![],
+            room_tracking_enabled: true,
+            ignored_sensors: vec![],
+            circadian: false,
+        };
+        let mut strategy = create_test_setup_with_scene(vec![scene.clone()]);
+        let overlay = Overlay {
+            name: "movie".to_string(),
+            brightness: Some(40),
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
+            disabled_switches: vec!["zigbee2mqtt/light1".to_string()],
+            enabled_switches: vec![],
+            ignored_switches: vec![],
+            room_tracking_enabled: None,
+            ignored_sensors: vec![],
+            circadian: None,
+        };
+        let commands = strategy.handle(UpdateMessage::AddOverlay(overlay.clone()));
+        assert_eq!(commands.first().unwrap().state, SwitchState::Off);
+        assert_eq!(strategy.brightness, 40);
+        assert_eq!(strategy.status().scene, Some("evening".to_string()));
+        assert_eq!(strategy.status().overlays, vec!["movie".to_string()]);
+
+        strategy.handle(UpdateMessage::RemoveOverlay("movie".to_string()));
+        assert_eq!(strategy.brightness, 200);
+        assert!(strategy.disabled_switches.is_empty());
+        assert!(strategy.status().overlays.is_empty());
+
+        // a scene change removes all overlays
+        strategy.add_overlay(overlay.clone());
+        strategy.set_scene(&scene);
+        assert!(strategy.overlays.is_empty());
+        assert_eq!(strategy.brightness, 200);
+
+        // overrides and manual changes are kept until the next scene change
+        strategy.set_switch_override("zigbee2mqtt/light1".to_string(), Some(SwitchState::On));
+        strategy.handle(UpdateMessage::BrightnessChange(120));
+        strategy.handle(UpdateMessage::RoomTrackingChange(false));
+        let dimmed = Overlay {
+            name: "dimmed".to_string(),
+            brightness: None,
+            disabled_switches: vec![],
+            ..overlay.clone()
+        };
+        strategy.handle(UpdateMessage::AddOverlay(dimmed));
+        strategy.handle(UpdateMessage::RemoveOverlay("dimmed".to_string()));
+        assert_eq!(
+            strategy.status().switch_overrides.get("zigbee2mqtt/light1"),
+            Some(&SwitchState::On)
+        );
+        assert_eq!(strategy.brightness, 120);
+        assert!(!strategy.room_tracking_enabled);
+        assert_eq!(strategy.snapshot().brightness, Some(120));
+
+        // an overlay which sets the brightness is newer than the manual change
+        strategy.handle(UpdateMessage::AddOverlay(overlay));
+        assert_eq!(strategy.brightness, 40);
+        assert!(!strategy.room_tracking_enabled);
+    }
+
+    #[test]
+    fn test_update_circadian() {
+        let scene = Scene {
+            name: "day".to_string(),
+            brightness: 255,
+            color_temp: None,
+            color: None,
+            room_settings: HashMap::new(),
+            switch_settings: HashMap::new(),
+            disabled_switches: vec![],
+            enabled_switches: vec!["zigbee2mqtt/light1".to_string()],
+            ignored_switches: vec![],
+            room_tracking_enabled: false,
+            ignored_sensors: vec![],
+            circadian: true,
+        };
+        let mut strategy = create_test_setup_with_scene(vec![scene]);
+        strategy.circadian = Some(Circadian {
+            keyframes: vec![
+                Keyframe {
+                    time: "08:00".to_string(),
+                    brightness: 200,
+                    color_temp: Some(250),
+                },
+                Keyframe {
+                    time: "20:00".to_string(),
+                    brightness: 100,
+                    color_temp: Some(400),
+                },
+            ],
+            update_interval: 300,
+        });
+        // not applied yet
+        assert!(strategy.next_deadline().unwrap() <= Instant::now());
+        let instant = Instant::now();
+        strategy.update_circadian_at(instant, 8 * 60);
+        let commands = strategy.trigger_commands(false);
+        assert_eq!(commands.first().unwrap().brightness, 200);
+        assert_eq!(commands.first().unwrap().color_temp, Some(250));
+        strategy.room_switches.get_mut(0).unwrap().state = SwitchState::On;
+
+        // rate limited by the update interval
+        strategy.update_circadian_at(instant + Duration::from_secs(60), 20 * 60);
+        assert!(strategy.trigger_commands(false).is_empty());
+
+        strategy.update_circadian_at(instant + Duration::from_secs(300), 20 * 60);
+        let commands = strategy.trigger_commands(false);
+        assert_eq!(commands.first().unwrap().brightness, 100);
+        assert_eq!(commands.first().unwrap().color_temp, Some(400));
+
+        // a passed deadline is applied by any message, not only by Ping
+        strategy.circadian_updated = Some(instant_from_the_past(400));
+        strategy.handle(UpdateMessage::BrightnessChange(50));
+        assert!(strategy.circadian_updated.unwrap().elapsed() < Duration::from_secs(5));
+        // the manual brightness survives the next curve update
+        assert_eq!(strategy.brightness, 50);
+        strategy.update_circadian_at(Instant::now() + Duration::from_secs(300), 8 * 60);
+        assert_eq!(strategy.brightness, 50);
+        assert_eq!(strategy.color_temp, Some(250));
+    }
 }
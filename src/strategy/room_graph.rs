@@ -0,0 +1,147 @@
+use crate::configuration::Room;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+/// Adjacency graph of the rooms, used to reject impossible
+/// current room transitions.
+pub struct RoomGraph {
+    adjacent_rooms: HashMap<String, HashSet<String>>,
+    handover_delays: HashMap<String, Duration>,
+}
+
+impl RoomGraph {
+    pub fn new(rooms: &[Room]) -> Self {
+        let mut adjacent_rooms: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut handover_delays = HashMap::new();
+        for room in rooms.iter() {
+            adjacent_rooms.entry(room.name.clone()).or_default();
+            handover_delays.insert(room.name.clone(), Duration::from_secs(room.handover_delay));
+            for adjacent_room in room.adjacent_rooms.iter() {
+                // connections work in both directions
+                adjacent_rooms
+                    .entry(room.name.clone())
+                    .or_default()
+                    .insert(adjacent_room.clone());
+                adjacent_rooms
+                    .entry(adjacent_room.clone())
+                    .or_default()
+                    .insert(room.name.clone());
+            }
+        }
+        RoomGraph {
+            adjacent_rooms,
+            handover_delays,
+        }
+    }
+
+    /// rooms that are not part of the graph are adjacent to every room
+    pub fn is_adjacent(&self, from: &str, to: &str) -> bool {
+        if from == to {
+            return true;
+        }
+        match (self.adjacent_rooms.get(from), self.adjacent_rooms.get(to)) {
+            (Some(adjacent_rooms), Some(_)) => adjacent_rooms.contains(to),
+            _ => true,
+        }
+    }
+
+    /// is there a path from `from` to `to` where every
+    /// intermediate room is `passable`
+    pub fn has_path<F>(&self, from: &str, to: &str, passable: F) -> bool
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        visited.insert(from.to_string());
+        queue.push_back(from.to_string());
+        while let Some(room) = queue.pop_front() {
+            if self.is_adjacent(&room, to) {
+                return true;
+            }
+            let adjacent_rooms = match self.adjacent_rooms.get(&room) {
+                Some(adjacent_rooms) => adjacent_rooms,
+                None => continue,
+            };
+            for adjacent_room in adjacent_rooms.iter() {
+                if visited.contains(adjacent_room) || !passable(adjacent_room) {
+                    continue;
+                }
+                visited.insert(adjacent_room.clone());
+                queue.push_back(adjacent_room.clone());
+            }
+        }
+        false
+    }
+
+    /// how long a room must be present to take over from a room
+    /// that is not adjacent.
+    pub fn get_handover_delay(&self, room: &str) -> Duration {
+        self.handover_delays
+            .get(room)
+            .cloned()
+            .unwrap_or_else(|| Duration::from_secs(Room::default_handover_delay()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_room(name: &str, adjacent_rooms: Vec<&str>) -> Room {
+        Room {
+            name: name.to_string(),
+            adjacent_rooms: adjacent_rooms.iter().map(|room| room.to_string()).collect(),
+            handover_delay: 10,
+        }
+    }
+
+    fn create_test_graph() -> RoomGraph {
+        RoomGraph::new(&[
+            create_room("bed_room", vec!["floor_room"]),
+            create_room("floor_room", vec!["kitchen_room", "living_room"]),
+            create_room("kitchen_room", vec![]),
+            create_room("living_room", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_is_adjacent() {
+        let graph = create_test_graph();
+        assert!(graph.is_adjacent("bed_room", "floor_room"));
+        assert!(graph.is_adjacent("floor_room", "bed_room"));
+        assert!(graph.is_adjacent("kitchen_room", "floor_room"));
+        assert!(!graph.is_adjacent("bed_room", "kitchen_room"));
+        assert!(!graph.is_adjacent("kitchen_room", "living_room"));
+    }
+
+    #[test]
+    fn test_unknown_rooms_are_adjacent() {
+        let graph = create_test_graph();
+        assert!(graph.is_adjacent("bed_room", "bath_room"));
+        assert!(graph.is_adjacent("bath_room", "kitchen_room"));
+        let graph = RoomGraph::new(&[]);
+        assert!(graph.is_adjacent("bed_room", "kitchen_room"));
+    }
+
+    #[test]
+    fn test_has_path() {
+        let graph = create_test_graph();
+        assert!(graph.has_path("bed_room", "kitchen_room", |room| room == "floor_room"));
+        assert!(!graph.has_path("bed_room", "kitchen_room", |_| false));
+        assert!(graph.has_path("bed_room", "floor_room", |_| false));
+    }
+
+    #[test]
+    fn test_handover_delay() {
+        let graph = create_test_graph();
+        assert_eq!(
+            graph.get_handover_delay("bed_room"),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            graph.get_handover_delay("bath_room"),
+            Duration::from_secs(Room::default_handover_delay())
+        );
+    }
+}
@@ -1,15 +1,43 @@
+use crate::configuration::SensorKind;
 use crate::strategy::sensor_states::{SensorMemoryNaiveState, SensorMemoryState};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 // todo: rename it
 pub struct SensorMemory {
     pub delay: Duration,
     pub state: SensorMemoryState,
+    pub kind: SensorKind,
+    /// expected max time between two messages
+    pub heartbeat: Option<Duration>,
+    /// stale sensors are not considered present
+    pub ignore_when_stale: bool,
+    /// last time a message of this sensor was received
+    pub last_seen: Instant,
 }
 
 impl SensorMemory {
+    /// the sensor didn't send a message for longer than its heartbeat
+    pub fn is_stale(&self) -> bool {
+        match self.heartbeat {
+            Some(heartbeat) => self.last_seen.elapsed() > heartbeat,
+            None => false,
+        }
+    }
+
+    /// present, stale sensors are not present if they are ignored when stale
+    pub fn is_present(&self) -> bool {
+        self.state == SensorMemoryState::Present && !(self.ignore_when_stale && self.is_stale())
+    }
+
     pub fn get_naive_state(&self, look_ahead: Duration) -> SensorMemoryNaiveState {
-        match self.state {
+        let state = match self.state {
+            // a stale present sensor is absent since it was seen the last time
+            SensorMemoryState::Present if !self.is_present() => {
+                SensorMemoryState::AbsentSince(self.last_seen)
+            }
+            ref state => state.clone(),
+        };
+        match state {
             SensorMemoryState::Uninitialized => SensorMemoryNaiveState::Uninitialized,
             SensorMemoryState::Present => SensorMemoryNaiveState::Present,
             SensorMemoryState::AbsentSince(instant) => {
@@ -27,7 +55,6 @@ impl SensorMemory {
 #[cfg(test)]
 mod tests_sensor_memory {
     use super::*;
-    use std::time::Instant;
 
     #[test]
     fn test_get_naive_state_1() {
@@ -35,6 +62,10 @@ mod tests_sensor_memory {
         let sensor_memory = SensorMemory {
             delay: Duration::from_secs(60),
             state: SensorMemoryState::AbsentSince(instant),
+            kind: SensorKind::Motion,
+            heartbeat: None,
+            ignore_when_stale: false,
+            last_seen: Instant::now(),
         };
         assert_eq!(
             sensor_memory.get_naive_state(Duration::from_secs(0)),
@@ -48,6 +79,10 @@ mod tests_sensor_memory {
         let sensor_memory = SensorMemory {
             delay: Duration::from_secs(60),
             state: SensorMemoryState::AbsentSince(instant),
+            kind: SensorKind::Motion,
+            heartbeat: None,
+            ignore_when_stale: false,
+            last_seen: Instant::now(),
         };
         let naive_state = sensor_memory.get_naive_state(Duration::from_secs(0));
         assert_ne!(naive_state, SensorMemoryNaiveState::Present,);
@@ -60,4 +95,31 @@ mod tests_sensor_memory {
             _ => panic!("never gonna happen"),
         }
     }
+
+    #[test]
+    fn test_get_naive_state_stale() {
+        let mut sensor_memory = SensorMemory {
+            delay: Duration::from_secs(60),
+            state: SensorMemoryState::Present,
+            kind: SensorKind::Motion,
+            heartbeat: Some(Duration::from_secs(3600)),
+            ignore_when_stale: true,
+            last_seen: Instant::now() - Duration::from_secs(3660),
+        };
+        assert!(sensor_memory.is_stale());
+        assert!(!sensor_memory.is_present());
+        match sensor_memory.get_naive_state(Duration::from_secs(0)) {
+            SensorMemoryNaiveState::AbsentSince(duration) => {
+                assert!(duration >= Duration::from_secs(600));
+            }
+            _ => panic!("stale sensor should be absent"),
+        }
+
+        sensor_memory.ignore_when_stale = false;
+        assert!(sensor_memory.is_present());
+        assert_eq!(
+            sensor_memory.get_naive_state(Duration::from_secs(0)),
+            SensorMemoryNaiveState::Present
+        );
+    }
 }
@@ -0,0 +1,62 @@
+use light_control::configuration::{Configuration, SwitchState};
+use light_control::engine::create_engine;
+use light_control::{SensorChangeContent, UpdateMessage};
+use std::time::Instant;
+
+fn load_configuration() -> Configuration {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/home.json");
+    Configuration::load_from_file(path).expect("couldn't load example configuration")
+}
+
+#[test]
+fn test_sensor_update_turns_on_switches_of_the_room() {
+    let configuration = load_configuration();
+    let mut engine = create_engine(&configuration);
+    let payload = serde_json::from_str(r#"{"occupancy":true}"#).unwrap();
+    let (topic, state) = configuration
+        .get_update_sensor_for_topic("zigbee2mqtt/motion_sensor_2", &payload)
+        .unwrap();
+    let commands = engine.handle(UpdateMessage::SensorChange(
+        Instant::now(),
+        SensorChangeContent { topic, state },
+    ));
+    assert_eq!(commands.len(), 2);
+    for command in commands.iter() {
+        assert_eq!(command.state, SwitchState::On);
+        assert_eq!(command.brightness, 255);
+    }
+}
+
+#[test]
+fn test_retained_sensor_messages_seed_the_state() {
+    let configuration = load_configuration();
+    let mut engine = create_engine(&configuration);
+    // retained messages arrive right after subscribing, before any sensor is taken over
+    let payload = serde_json::from_str(r#"{"occupancy":true}"#).unwrap();
+    let (topic, state) = configuration
+        .get_update_sensor_for_topic("zigbee2mqtt/motion_sensor_2", &payload)
+        .unwrap();
+    engine.handle(UpdateMessage::SensorChange(
+        Instant::now(),
+        SensorChangeContent { topic, state },
+    ));
+    assert_eq!(engine.status().rooms.get("bed_room"), Some(&true));
+}
+
+#[test]
+fn test_scene_change_triggers_all_switches() {
+    let configuration = load_configuration();
+    let mut engine = create_engine(&configuration);
+    let scene = configuration.get_scene(&"night".to_string()).unwrap();
+    let commands = engine.handle(UpdateMessage::SceneChange(scene.clone()));
+    let command = commands
+        .iter()
+        .find(|command| command.topic == "stat/PAL04/RESULT")
+        .unwrap();
+    assert_eq!(command.state, SwitchState::Off);
+    let switch = configuration
+        .get_switch_for_topic(command.topic.clone())
+        .unwrap();
+    let (topic, _) = switch.get_topic_and_command(command.state, command.brightness, None, None);
+    assert_eq!(topic, "cmnd/PAL04/POWER");
+}